@@ -0,0 +1,118 @@
+use crate::math::Float;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+/// 三次浮点运算的相对误差上界，见 PBR 3.9.1 节
+const GAMMA_3: Float = 3. * Float::EPSILON / (1. - 3. * Float::EPSILON);
+
+/// 轴对齐包围盒
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    pub fn new(min: Vec3, max: Vec3) -> Self {
+        Aabb { min, max }
+    }
+
+    /// 不包含任何点的包围盒，与任意包围盒合并后得到后者自身
+    pub fn empty() -> Self {
+        Aabb {
+            min: Vec3::from(Float::INFINITY),
+            max: Vec3::from(Float::NEG_INFINITY),
+        }
+    }
+
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.element_wise(Float::min, &other.min),
+            max: self.max.element_wise(Float::max, &other.max),
+        }
+    }
+
+    pub fn include(&self, p: &Vec3) -> Aabb {
+        Aabb {
+            min: self.min.element_wise(Float::min, p),
+            max: self.max.element_wise(Float::max, p),
+        }
+    }
+
+    pub fn centroid(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vec3 {
+        self.max - self.min
+    }
+
+    /// 跨度最大的坐标轴，0、1、2 分别对应 x、y、z
+    pub fn longest_axis(&self) -> usize {
+        let e = self.extent();
+        if e.x >= e.y && e.x >= e.z {
+            0
+        } else if e.y >= e.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    /// 表面积，空包围盒的表面积为 0
+    pub fn surface_area(&self) -> Float {
+        let e = self.extent();
+        if e.x < 0. || e.y < 0. || e.z < 0. {
+            0.
+        } else {
+            2. * (e.x * e.y + e.y * e.z + e.z * e.x)
+        }
+    }
+
+    /// slab 法求交，只判断光线在 (t_min, t_max) 内是否穿过包围盒
+    pub fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> bool {
+        let mut t0 = t_min;
+        let mut t1 = t_max;
+        for axis in 0..3 {
+            let inv_d = 1. / axis_of(&ray.direction, axis);
+            let mut near = (axis_of(&self.min, axis) - axis_of(&ray.origin, axis)) * inv_d;
+            let mut far = (axis_of(&self.max, axis) - axis_of(&ray.origin, axis)) * inv_d;
+            if inv_d < 0. {
+                std::mem::swap(&mut near, &mut far);
+            }
+            // 略微放宽远端，避免舍入误差使掠射光线漏掉盒内的物体
+            far *= 1. + 2. * GAMMA_3;
+            // 光线与 slab 平行且起点恰在边界上时 near/far 为 NaN，此时不收紧区间
+            if near > t0 {
+                t0 = near;
+            }
+            if far < t1 {
+                t1 = far;
+            }
+            if t1 < t0 {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub(crate) fn axis_of(v: &Vec3, axis: usize) -> Float {
+    match axis {
+        0 => v.x,
+        1 => v.y,
+        _ => v.z,
+    }
+}
+
+#[test]
+fn test_aabb_hit() {
+    let aabb = Aabb::new(Vec3::new(-1., -1., -1.), Vec3::ones());
+    let ray = Ray::new(Vec3::new(0., 0., -5.), Vec3::new(0., 0., 1.));
+    assert!(aabb.hit(&ray, 0.001, Float::MAX));
+    assert!(!aabb.hit(&ray, 0.001, 3.));
+    let ray = Ray::new(Vec3::new(0., 2., -5.), Vec3::new(0., 0., 1.));
+    assert!(!aabb.hit(&ray, 0.001, Float::MAX));
+    assert_eq!(aabb.surface_area(), 24.);
+    assert_eq!(Aabb::empty().surface_area(), 0.);
+}
@@ -0,0 +1,223 @@
+//! 辅助通道（AOV）：摄影机光线首个交点处的反照率、法线、深度等，供降噪与后期合成使用
+//!
+//! 辅助通道与颜色使用相同的样本与滤波器权重累加，物体边缘处为相邻取值的加权平均；
+//! 物体编号例外，只保留像素内第一个样本的取值，始终是某个物体确切的编号。
+
+use std::str::FromStr;
+
+use crate::color::linear_to_srgb;
+use crate::hittable::HitRecord;
+use crate::math::Float;
+use crate::ray::Ray;
+use crate::sampler::hash;
+use crate::vec3::Vec3;
+use crate::world::World;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Aov {
+    /// 表面反照率，未击中时为背景颜色
+    Albedo,
+    /// 朝向摄影机一侧的世界空间着色法线
+    Normal,
+    /// 交点在视线方向上的距离
+    Depth,
+    /// 世界空间中的交点坐标
+    Position,
+    /// 物体在场景中的序号加一，未击中时为 0，三个分量相同；PNG 输出按序号哈希为伪彩色
+    ObjectId,
+}
+
+impl FromStr for Aov {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "albedo" => Ok(Aov::Albedo),
+            "normal" | "normals" => Ok(Aov::Normal),
+            "depth" | "z" => Ok(Aov::Depth),
+            "position" | "p" => Ok(Aov::Position),
+            "id" | "object" | "objectid" => Ok(Aov::ObjectId),
+            _ => Err(format!("Unknown AOV {}", s)),
+        }
+    }
+}
+
+impl Aov {
+    pub const ALL: [Aov; 5] = [
+        Aov::Albedo,
+        Aov::Normal,
+        Aov::Depth,
+        Aov::Position,
+        Aov::ObjectId,
+    ];
+
+    /// 用于文件名与 OpenEXR 通道名
+    pub fn name(&self) -> &'static str {
+        match self {
+            Aov::Albedo => "albedo",
+            Aov::Normal => "normal",
+            Aov::Depth => "depth",
+            Aov::Position => "position",
+            Aov::ObjectId => "id",
+        }
+    }
+
+    /// 光线 `ray` 首个交点 `rec` 处的取值，深度为单值通道，三个分量相同
+    pub fn evaluate(
+        &self,
+        ray: &Ray,
+        rec: Option<&HitRecord>,
+        world: &World,
+        forward: &Vec3,
+    ) -> Vec3 {
+        let rec = match rec {
+            Some(rec) => rec,
+            None if *self == Aov::Albedo => return world.background.color(&ray.direction.unit()),
+            None => return Vec3::zeros(),
+        };
+        match self {
+            Aov::Albedo => rec.material.albedo(rec),
+            Aov::Normal => rec.normal,
+            Aov::Depth => Vec3::ones() * (rec.p - ray.origin).dot(forward),
+            Aov::Position => rec.p,
+            Aov::ObjectId => Vec3::ones() * (rec.object_id + 1) as Float,
+        }
+    }
+
+    /// 将逐行排列的 RGB 取值编码为便于查看的 8 位像素：法线映射到 [0, 1]，深度与坐标按全图范围归一化
+    pub fn to_srgb8(&self, values: &[Float]) -> Vec<u8> {
+        let encode = |v: Float| (v.clamp(0., 1.) * 255.).round() as u8;
+        match self {
+            Aov::Albedo => values.iter().map(|&v| encode(linear_to_srgb(v))).collect(),
+            Aov::ObjectId => values
+                .iter()
+                .step_by(3)
+                .flat_map(|&id| {
+                    if id <= 0. {
+                        return [0; 3];
+                    }
+                    let h = hash(&[id as u64]);
+                    // 避开过暗的颜色，与未击中的黑色区分
+                    let channel = |shift: u32| 51 + ((h >> shift) & 0xff) as u8 / 5 * 4;
+                    [channel(0), channel(8), channel(16)]
+                })
+                .collect(),
+            Aov::Normal => values.iter().map(|&v| encode(v * 0.5 + 0.5)).collect(),
+            Aov::Depth => {
+                let max = values.iter().copied().fold(0., Float::max);
+                let scale = if max > 0. { 1. / max } else { 0. };
+                values.iter().map(|&v| encode(v * scale)).collect()
+            }
+            Aov::Position => {
+                let mut min = [Float::INFINITY; 3];
+                let mut max = [Float::NEG_INFINITY; 3];
+                for pixel in values.chunks_exact(3) {
+                    for c in 0..3 {
+                        min[c] = min[c].min(pixel[c]);
+                        max[c] = max[c].max(pixel[c]);
+                    }
+                }
+                values
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &v)| {
+                        let c = i % 3;
+                        let range = max[c] - min[c];
+                        encode(if range > 0. { (v - min[c]) / range } else { 0. })
+                    })
+                    .collect()
+            }
+        }
+    }
+}
+
+/// 启用的辅助通道集合，按 `Aov::ALL` 的顺序遍历
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct AovSet(u8);
+
+impl AovSet {
+    pub fn new() -> AovSet {
+        AovSet(0)
+    }
+
+    pub fn with(mut self, aov: Aov) -> AovSet {
+        self.0 |= 1 << aov as u8;
+        self
+    }
+
+    pub fn contains(&self, aov: Aov) -> bool {
+        self.0 & (1 << aov as u8) != 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.count_ones() as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Aov> {
+        let set = *self;
+        Aov::ALL.into_iter().filter(move |&aov| set.contains(aov))
+    }
+
+    pub fn bits(&self) -> u8 {
+        self.0
+    }
+}
+
+impl FromIterator<Aov> for AovSet {
+    fn from_iter<I: IntoIterator<Item = Aov>>(iter: I) -> Self {
+        iter.into_iter().fold(AovSet::new(), AovSet::with)
+    }
+}
+
+/// 以逗号分隔的通道名，例如 `normal,depth`
+impl FromStr for AovSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        s.split(',')
+            .filter(|name| !name.trim().is_empty())
+            .map(Aov::from_str)
+            .collect()
+    }
+}
+
+/// 在扩展名前插入通道名，例如 `out.png` 的法线通道保存为 `out.normal.png`
+pub fn aov_filename(filename: &str, aov: Aov) -> String {
+    let path = std::path::Path::new(filename);
+    match (path.file_stem(), path.extension()) {
+        (Some(stem), Some(extension)) => path
+            .with_file_name(format!(
+                "{}.{}.{}",
+                stem.to_string_lossy(),
+                aov.name(),
+                extension.to_string_lossy()
+            ))
+            .to_string_lossy()
+            .into_owned(),
+        _ => format!("{}.{}", filename, aov.name()),
+    }
+}
+
+#[test]
+fn test_aov_set() {
+    let set: AovSet = "depth, normal,,albedo".parse().unwrap();
+    assert_eq!(set.len(), 3);
+    assert!(set.contains(Aov::Normal) && !set.contains(Aov::Position));
+    // 遍历顺序与输入顺序无关
+    assert_eq!(
+        set.iter().collect::<Vec<_>>(),
+        vec![Aov::Albedo, Aov::Normal, Aov::Depth]
+    );
+    assert!("normal,speed".parse::<AovSet>().is_err());
+    assert!(AovSet::new().is_empty());
+
+    assert_eq!(
+        aov_filename("out/image.png", Aov::Normal),
+        "out/image.normal.png"
+    );
+    assert_eq!(aov_filename("image.exr", Aov::ObjectId), "image.id.exr");
+}
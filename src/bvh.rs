@@ -0,0 +1,317 @@
+use std::sync::Arc;
+
+use crate::aabb::{axis_of, Aabb};
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::math::Float;
+use crate::ray::Ray;
+use crate::vec3::Vec3;
+
+/// SAH 分桶数
+const NUM_BUCKETS: usize = 12;
+/// 叶节点最多容纳的物体数
+const MAX_LEAF_SIZE: usize = 4;
+/// 遍历一个内部节点相对于求交一个物体的代价
+const TRAVERSAL_COST: Float = 0.125;
+
+enum BvhNodeKind {
+    /// 叶节点，引用 `Bvh::hittables[first..first + count]`
+    Leaf { first: usize, count: usize },
+    /// 内部节点，左孩子紧随其后，右孩子位于 `second`
+    Interior { second: usize, axis: usize },
+}
+
+struct BvhNode {
+    bounds: Aabb,
+    kind: BvhNodeKind,
+}
+
+/// 按表面积启发式 (SAH) 构建的层次包围盒，结点展平存放于数组中
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    hittables: Vec<Arc<dyn Hittable>>,
+    /// `hittables` 中各物体在构建前的下标
+    original_indices: Vec<usize>,
+}
+
+struct BuildItem {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vec3,
+}
+
+impl Bvh {
+    pub fn new(hittables: Vec<Arc<dyn Hittable>>) -> Bvh {
+        let mut items = hittables
+            .iter()
+            .enumerate()
+            .map(|(index, obj)| {
+                let bounds = obj.bounding_box();
+                BuildItem {
+                    index,
+                    bounds,
+                    centroid: bounds.centroid(),
+                }
+            })
+            .collect::<Vec<_>>();
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(2 * items.len()),
+            hittables: Vec::with_capacity(items.len()),
+            original_indices: Vec::with_capacity(items.len()),
+        };
+        if !items.is_empty() {
+            bvh.build(&mut items, &hittables);
+        }
+        bvh
+    }
+
+    fn build(&mut self, items: &mut [BuildItem], source: &[Arc<dyn Hittable>]) -> usize {
+        let bounds = items
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.union(&item.bounds));
+        let node = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounds,
+            kind: BvhNodeKind::Leaf { first: 0, count: 0 },
+        });
+
+        let split = if items.len() > 1 {
+            Self::find_split(items, &bounds)
+        } else {
+            None
+        };
+        match split {
+            Some((axis, mid)) => {
+                let (left, right) = items.split_at_mut(mid);
+                self.build(left, source);
+                let second = self.build(right, source);
+                self.nodes[node].kind = BvhNodeKind::Interior { second, axis };
+            }
+            None => {
+                let first = self.hittables.len();
+                for item in items.iter() {
+                    self.hittables.push(source[item.index].clone());
+                    self.original_indices.push(item.index);
+                }
+                self.nodes[node].kind = BvhNodeKind::Leaf {
+                    first,
+                    count: items.len(),
+                };
+            }
+        }
+        node
+    }
+
+    /// 寻找 SAH 代价最小的划分，返回划分轴与左半部分的物体数；若不划分更优则返回 `None`
+    fn find_split(items: &mut [BuildItem], bounds: &Aabb) -> Option<(usize, usize)> {
+        let centroid_bounds = items
+            .iter()
+            .fold(Aabb::empty(), |acc, item| acc.include(&item.centroid));
+        let axis = centroid_bounds.longest_axis();
+        let lo = axis_of(&centroid_bounds.min, axis);
+        let hi = axis_of(&centroid_bounds.max, axis);
+        if hi <= lo {
+            // 所有质心重合，无法划分
+            return if items.len() > MAX_LEAF_SIZE {
+                Some((axis, items.len() / 2))
+            } else {
+                None
+            };
+        }
+
+        let bucket_of = |item: &BuildItem| -> usize {
+            let offset = (axis_of(&item.centroid, axis) - lo) / (hi - lo);
+            ((offset * NUM_BUCKETS as Float) as usize).min(NUM_BUCKETS - 1)
+        };
+        let mut counts = [0usize; NUM_BUCKETS];
+        let mut boxes = [Aabb::empty(); NUM_BUCKETS];
+        for item in items.iter() {
+            let b = bucket_of(item);
+            counts[b] += 1;
+            boxes[b] = boxes[b].union(&item.bounds);
+        }
+
+        // 自左向右、自右向左各扫描一遍，累计每个划分位置两侧的包围盒与物体数
+        let mut left_area = [0 as Float; NUM_BUCKETS - 1];
+        let mut left_count = [0usize; NUM_BUCKETS - 1];
+        let (mut acc, mut n) = (Aabb::empty(), 0);
+        for i in 0..NUM_BUCKETS - 1 {
+            acc = acc.union(&boxes[i]);
+            n += counts[i];
+            left_area[i] = acc.surface_area();
+            left_count[i] = n;
+        }
+        let (mut best_cost, mut best_split) = (Float::INFINITY, 0);
+        let (mut acc, mut n) = (Aabb::empty(), 0);
+        for i in (0..NUM_BUCKETS - 1).rev() {
+            acc = acc.union(&boxes[i + 1]);
+            n += counts[i + 1];
+            let cost = left_count[i] as Float * left_area[i] + n as Float * acc.surface_area();
+            if cost < best_cost {
+                best_cost = cost;
+                best_split = i;
+            }
+        }
+
+        let area = bounds.surface_area();
+        let split_cost = if area > 0. {
+            TRAVERSAL_COST + best_cost / area
+        } else {
+            TRAVERSAL_COST
+        };
+        let leaf_cost = items.len() as Float;
+        if items.len() <= MAX_LEAF_SIZE && leaf_cost <= split_cost {
+            return None;
+        }
+
+        let mid = partition(items, |item| bucket_of(item) <= best_split);
+        if mid == 0 || mid == items.len() {
+            Some((axis, items.len() / 2))
+        } else {
+            Some((axis, mid))
+        }
+    }
+
+    fn hit_leaf(&self, first: usize, count: usize, ray: &Ray, t_min: Float, nearest: &mut Nearest) {
+        for index in first..first + count {
+            let original = self.original_indices[index];
+            // 距离相同时取原始顺序靠前的物体，与线性扫描保持一致
+            let t_max = if nearest.rec.is_some() && original < nearest.index {
+                next_up(nearest.t)
+            } else {
+                nearest.t
+            };
+            if let Some(rec) = self.hittables[index].hit(ray, t_min, t_max) {
+                nearest.t = rec.t;
+                nearest.index = original;
+                nearest.rec = Some(rec);
+            }
+        }
+    }
+}
+
+/// 遍历过程中目前最近的交点
+struct Nearest {
+    rec: Option<HitRecord>,
+    t: Float,
+    /// 交点所属物体在构建前的下标
+    index: usize,
+}
+
+impl From<HittableList> for Bvh {
+    fn from(list: HittableList) -> Self {
+        Bvh::new(list.into_objects())
+    }
+}
+
+impl Hittable for Bvh {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let negative = [
+            ray.direction.x < 0.,
+            ray.direction.y < 0.,
+            ray.direction.z < 0.,
+        ];
+        let mut nearest = Nearest {
+            rec: None,
+            t: t_max,
+            index: usize::MAX,
+        };
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(node) = stack.pop() {
+            let BvhNode { bounds, kind } = &self.nodes[node];
+            if !bounds.hit(ray, t_min, next_up(nearest.t)) {
+                continue;
+            }
+            match *kind {
+                BvhNodeKind::Leaf { first, count } => {
+                    self.hit_leaf(first, count, ray, t_min, &mut nearest)
+                }
+                BvhNodeKind::Interior { second, axis } => {
+                    // 先访问沿光线方向较近的孩子
+                    if negative[axis] {
+                        stack.push(node + 1);
+                        stack.push(second);
+                    } else {
+                        stack.push(second);
+                        stack.push(node + 1);
+                    }
+                }
+            }
+        }
+        nearest.rec.map(|mut rec| {
+            rec.object_id = nearest.index;
+            rec
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map(|node| node.bounds)
+            .unwrap_or_else(Aabb::empty)
+    }
+}
+
+/// 原地划分，满足谓词的元素移至前部，返回其数量
+fn partition<T, F>(items: &mut [T], pred: F) -> usize
+where
+    F: Fn(&T) -> bool,
+{
+    let mut mid = 0;
+    for i in 0..items.len() {
+        if pred(&items[i]) {
+            items.swap(i, mid);
+            mid += 1;
+        }
+    }
+    mid
+}
+
+/// 大于 `x` 的最小浮点数
+fn next_up(x: Float) -> Float {
+    if x.is_nan() || x == Float::INFINITY {
+        x
+    } else if x == 0. {
+        Float::from_bits(1)
+    } else if x > 0. {
+        Float::from_bits(x.to_bits() + 1)
+    } else {
+        Float::from_bits(x.to_bits() - 1)
+    }
+}
+
+#[test]
+fn test_bvh_matches_linear_scan() {
+    use crate::sampler::{RandomSampler, Sampler};
+    use crate::scene::{cornell_smoke, random_final_scene};
+
+    // Cornell 盒中的烟雾检验参与介质：自由程只取决于光线携带的随机数，与遍历顺序无关
+    let scenes = [
+        (random_final_scene(1.5, 0), Vec3::zeros(), 12.),
+        (cornell_smoke(1.), Vec3::ones() * 277.5, 300.),
+    ];
+    for ((_, world, _, _, _), center, range) in scenes {
+        let bvh = Bvh::new(world.objects().to_vec());
+        let mut sampler = RandomSampler::new(0);
+        for _ in 0..10000 {
+            let origin = center + Vec3::random_between(&mut sampler, -range, range);
+            let ray = Ray::new(origin, Vec3::random_unit_vector(&mut sampler))
+                .with_medium_sample(sampler.random());
+            let expected = world.hit(&ray, 0.001, Float::MAX);
+            let actual = bvh.hit(&ray, 0.001, Float::MAX);
+            match (expected, actual) {
+                (None, None) => {}
+                (Some(e), Some(a)) => {
+                    assert_eq!(e.t, a.t);
+                    assert_eq!(e.p, a.p);
+                    assert_eq!(e.normal, a.normal);
+                    assert!(Arc::ptr_eq(&e.material, &a.material));
+                }
+                _ => panic!("BVH disagrees with linear scan for {:?}", ray),
+            }
+        }
+    }
+}
@@ -1,9 +1,13 @@
 use crate::math::Float;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::Vec3;
 
 pub trait Camera: Sync + Send {
-    fn ray(&self, u: Float, v: Float) -> Ray;
+    fn ray(&self, u: Float, v: Float, sampler: &mut dyn Sampler) -> Ray;
+
+    /// 视线方向的单位向量，交点在其上的投影即摄影机空间中的深度
+    fn forward(&self) -> Vec3;
 }
 
 pub struct SimpleCamera {
@@ -35,12 +39,15 @@ impl SimpleCamera {
 unsafe impl Sync for SimpleCamera {}
 
 impl Camera for SimpleCamera {
-    fn ray(&self, u: Float, v: Float) -> Ray {
-        Ray {
-            origin: self.origin,
-            direction: self.lower_left + self.horizontal * u + self.vertical * v - self.origin,
-            t: 0.0,
-        }
+    fn ray(&self, u: Float, v: Float, _sampler: &mut dyn Sampler) -> Ray {
+        Ray::new(
+            self.origin,
+            self.lower_left + self.horizontal * u + self.vertical * v - self.origin,
+        )
+    }
+
+    fn forward(&self) -> Vec3 {
+        Vec3::new(0., 0., -1.)
     }
 }
 
@@ -52,8 +59,12 @@ pub struct PositionalCamera {
     pub vertical: Vec3,
     pub lower_left: Vec3,
     pub lens_radius: Float,
+    /// 快门开启与关闭的时刻，光线的时间在两者间均匀采样
+    pub shutter_open: Float,
+    pub shutter_close: Float,
     u: Vec3,
     v: Vec3,
+    w: Vec3,
 }
 
 unsafe impl Sync for PositionalCamera {}
@@ -90,22 +101,39 @@ impl PositionalCamera {
             vertical,
             lower_left,
             lens_radius: aperture / 2.,
+            shutter_open: 0.,
+            shutter_close: 0.,
             u,
             v,
+            w,
         }
     }
+
+    /// 设置快门时间，用于渲染运动模糊
+    pub fn with_shutter(mut self, open: Float, close: Float) -> PositionalCamera {
+        self.shutter_open = open;
+        self.shutter_close = close;
+        self
+    }
 }
 
 impl Camera for PositionalCamera {
-    fn ray(&self, s: Float, t: Float) -> Ray {
-        let rd = Vec3::random_in_unit_sphere() * self.lens_radius;
+    fn ray(&self, s: Float, t: Float, sampler: &mut dyn Sampler) -> Ray {
+        let rd = Vec3::random_in_unit_sphere(sampler) * self.lens_radius;
         let offset = self.u * rd.x + self.v * rd.y;
-        Ray {
-            origin: self.origin + offset,
-            direction: self.lower_left + self.horizontal * s + self.vertical * t
-                - self.origin
-                - offset,
-            t: 0.0,
-        }
+        // 快门瞬时开合时不采样时间，不消耗随机数
+        let time = if self.shutter_close > self.shutter_open {
+            sampler.random_between(self.shutter_open, self.shutter_close)
+        } else {
+            self.shutter_open
+        };
+        Ray::new_at(
+            self.origin + offset,
+            self.lower_left + self.horizontal * s + self.vertical * t - self.origin - offset,
+            time,
+        )
+    }
+    fn forward(&self) -> Vec3 {
+        -self.w
     }
 }
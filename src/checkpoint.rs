@@ -0,0 +1,215 @@
+//! 渲染检查点：保存胶片的累加结果与各像素的样本统计，中断后可以从中继续渲染
+
+use std::fmt;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::math::Float;
+use crate::sampler::hash;
+use crate::vec3::Vec3;
+
+const MAGIC: &[u8; 8] = b"TRCKPT02";
+
+#[derive(Debug)]
+pub enum CheckpointError {
+    IOError(std::io::Error),
+    /// 文件不是检查点或已经损坏
+    InvalidFormat,
+    /// 检查点由不同的场景或渲染参数生成
+    Mismatch,
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CheckpointError::IOError(e) => write!(f, "{}", e),
+            CheckpointError::InvalidFormat => write!(f, "not a valid checkpoint file"),
+            CheckpointError::Mismatch => {
+                write!(f, "checkpoint was made with a different scene or settings")
+            }
+        }
+    }
+}
+
+/// 单个像素的累加状态
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct PixelState {
+    /// 经滤波器加权的颜色之和与权重之和
+    pub sum: Vec3,
+    pub weight: Float,
+    /// 以该像素为起点采集的样本数，即采样器下一个样本的序号
+    pub count: usize,
+    /// 样本亮度的均值与 Welford 算法中的平方差之和，仅用于自适应采样
+    pub mean: Float,
+    pub m2: Float,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Checkpoint {
+    /// 场景与渲染参数的哈希，恢复时必须一致
+    pub fingerprint: u64,
+    pub width: usize,
+    pub height: usize,
+    /// 已经采集的样本总数
+    pub spent: usize,
+    /// 逐行排列的像素状态
+    pub pixels: Vec<PixelState>,
+    /// 各像素辅助通道的加权和，每个像素依次存放启用的各通道
+    pub aovs: Vec<Vec3>,
+}
+
+impl Checkpoint {
+    /// 先写入临时文件再重命名，写入中途被终止也不会破坏已有的检查点
+    #[allow(clippy::unnecessary_cast)] // 启用双精度时 Float 为 f64
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), CheckpointError> {
+        let path = path.as_ref();
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let mut writer = BufWriter::new(File::create(&temp).map_err(CheckpointError::IOError)?);
+        let mut write = |bytes: &[u8]| writer.write_all(bytes).map_err(CheckpointError::IOError);
+        write(MAGIC)?;
+        for value in [
+            self.fingerprint,
+            self.width as u64,
+            self.height as u64,
+            self.spent as u64,
+            self.aovs.len() as u64,
+        ] {
+            write(&value.to_le_bytes())?;
+        }
+        // 浮点数一律按 f64 保存，与精度设置无关
+        for pixel in &self.pixels {
+            for value in [
+                pixel.sum.x,
+                pixel.sum.y,
+                pixel.sum.z,
+                pixel.weight,
+                pixel.mean,
+                pixel.m2,
+            ] {
+                write(&(value as f64).to_le_bytes())?;
+            }
+            write(&(pixel.count as u64).to_le_bytes())?;
+        }
+        for sum in &self.aovs {
+            for value in [sum.x, sum.y, sum.z] {
+                write(&(value as f64).to_le_bytes())?;
+            }
+        }
+        writer.flush().map_err(CheckpointError::IOError)?;
+        drop(writer);
+        std::fs::rename(&temp, path).map_err(CheckpointError::IOError)
+    }
+
+    #[allow(clippy::unnecessary_cast)] // 启用双精度时 Float 为 f64
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Checkpoint, CheckpointError> {
+        let mut reader = BufReader::new(File::open(path).map_err(CheckpointError::IOError)?);
+        let mut magic = [0; 8];
+        read_exact(&mut reader, &mut magic)?;
+        if &magic != MAGIC {
+            return Err(CheckpointError::InvalidFormat);
+        }
+        let fingerprint = read_u64(&mut reader)?;
+        let width = read_u64(&mut reader)? as usize;
+        let height = read_u64(&mut reader)? as usize;
+        let spent = read_u64(&mut reader)? as usize;
+        let aov_count = read_u64(&mut reader)? as usize;
+        let size = width
+            .checked_mul(height)
+            .ok_or(CheckpointError::InvalidFormat)?;
+        let mut pixels = Vec::with_capacity(size.min(1 << 24));
+        for _ in 0..size {
+            let mut values = [0.; 6];
+            for value in values.iter_mut() {
+                *value = f64::from_bits(read_u64(&mut reader)?) as Float;
+            }
+            pixels.push(PixelState {
+                sum: Vec3::new(values[0], values[1], values[2]),
+                weight: values[3],
+                mean: values[4],
+                m2: values[5],
+                count: read_u64(&mut reader)? as usize,
+            });
+        }
+        let mut aovs = Vec::with_capacity(aov_count.min(1 << 24));
+        for _ in 0..aov_count {
+            let mut values = [0.; 3];
+            for value in values.iter_mut() {
+                *value = f64::from_bits(read_u64(&mut reader)?) as Float;
+            }
+            aovs.push(Vec3::new(values[0], values[1], values[2]));
+        }
+        if reader.read(&mut [0]).map_err(CheckpointError::IOError)? != 0 {
+            return Err(CheckpointError::InvalidFormat);
+        }
+        Ok(Checkpoint {
+            fingerprint,
+            width,
+            height,
+            spent,
+            pixels,
+            aovs,
+        })
+    }
+}
+
+fn read_exact<R: Read>(reader: &mut R, buffer: &mut [u8]) -> Result<(), CheckpointError> {
+    reader.read_exact(buffer).map_err(|e| match e.kind() {
+        std::io::ErrorKind::UnexpectedEof => CheckpointError::InvalidFormat,
+        _ => CheckpointError::IOError(e),
+    })
+}
+
+fn read_u64<R: Read>(reader: &mut R) -> Result<u64, CheckpointError> {
+    let mut bytes = [0; 8];
+    read_exact(reader, &mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+/// 场景描述文件等任意字节串的哈希
+pub fn hash_bytes(bytes: &[u8]) -> u64 {
+    let words: Vec<u64> = bytes
+        .chunks(8)
+        .map(|chunk| {
+            let mut word = [0; 8];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u64::from_le_bytes(word)
+        })
+        .collect();
+    hash(&[hash(&words), bytes.len() as u64])
+}
+
+#[test]
+fn test_checkpoint_round_trip() {
+    let checkpoint = Checkpoint {
+        fingerprint: 0x0123456789abcdef,
+        width: 3,
+        height: 2,
+        spent: 42,
+        pixels: (0..6)
+            .map(|i| PixelState {
+                sum: Vec3::new(i as Float, 0.5, 1e-3),
+                weight: 7.,
+                count: i * 7,
+                mean: 0.25,
+                m2: 1. / 3.,
+            })
+            .collect(),
+        aovs: (0..12).map(|i| Vec3::new(i as Float, -1., 0.125)).collect(),
+    };
+    let path = std::env::temp_dir().join("toy-raytracer-checkpoint-test.ckpt");
+    checkpoint.save(&path).unwrap();
+    assert_eq!(Checkpoint::load(&path).unwrap(), checkpoint);
+
+    // 截断的文件不能被当作检查点读入
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    assert!(matches!(
+        Checkpoint::load(&path),
+        Err(CheckpointError::InvalidFormat)
+    ));
+    std::fs::remove_file(&path).unwrap();
+
+    assert_ne!(hash_bytes(b"weekend"), hash_bytes(b"weekend\0"));
+}
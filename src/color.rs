@@ -13,9 +13,9 @@ impl Color {
 
     pub fn clamp(&self) -> Color {
         Color {
-            r: self.r.max(0.).min(1.),
-            g: self.g.max(0.).min(1.),
-            b: self.b.max(0.).min(1.),
+            r: self.r.clamp(0., 1.),
+            g: self.g.clamp(0., 1.),
+            b: self.b.clamp(0., 1.),
         }
     }
 
@@ -32,3 +32,31 @@ impl Color {
         vec![r, g, b]
     }
 }
+
+/// sRGB 编码值转换为线性值
+pub fn srgb_to_linear(c: Float) -> Float {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// 线性值转换为 sRGB 编码值
+pub fn linear_to_srgb(c: Float) -> Float {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+#[test]
+fn test_srgb_round_trip() {
+    for i in 0..=100 {
+        let c = i as Float / 100.;
+        assert!((srgb_to_linear(linear_to_srgb(c)) - c).abs() < 1e-5);
+    }
+    assert_eq!(linear_to_srgb(0.), 0.);
+    assert!((linear_to_srgb(1.) - 1.).abs() < 1e-6);
+}
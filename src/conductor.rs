@@ -0,0 +1,131 @@
+//! 导体的复折射率 η + ik 与完整的菲涅尔方程
+//!
+//! 预设取自常见金属的实测光谱数据，按 RGB 三个通道分别积分，入射侧介质为空气。
+
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::math::Float;
+use crate::vec3::Vec3;
+
+/// 逐通道的复折射率
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ComplexIor {
+    /// 折射率的实部
+    pub eta: Vec3,
+    /// 消光系数
+    pub k: Vec3,
+}
+
+impl ComplexIor {
+    pub fn new(eta: Vec3, k: Vec3) -> ComplexIor {
+        ComplexIor { eta, k }
+    }
+
+    /// 入射角余弦为 `cos_i` 时的反射率
+    pub fn fresnel(&self, cos_i: Float) -> Vec3 {
+        Vec3::new(
+            fresnel_conductor(cos_i, self.eta.x, self.k.x),
+            fresnel_conductor(cos_i, self.eta.y, self.k.y),
+            fresnel_conductor(cos_i, self.eta.z, self.k.z),
+        )
+    }
+}
+
+/// 导体界面的非偏振菲涅尔反射率，`eta` 与 `k` 为导体相对入射侧介质的复折射率
+pub fn fresnel_conductor(cos_i: Float, eta: Float, k: Float) -> Float {
+    let cos2 = cos_i.clamp(0., 1.).powi(2);
+    let sin2 = 1. - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2. * cos_i.clamp(0., 1.) * a;
+    let r_s = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let r_p = r_s * (t3 - t4) / (t3 + t4);
+    (r_s + r_p) * 0.5
+}
+
+/// 常见金属
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ConductorPreset {
+    Gold,
+    Silver,
+    Copper,
+    #[serde(alias = "aluminum")]
+    Aluminium,
+    #[serde(alias = "chromium")]
+    Chrome,
+}
+
+impl FromStr for ConductorPreset {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "gold" | "au" => Ok(ConductorPreset::Gold),
+            "silver" | "ag" => Ok(ConductorPreset::Silver),
+            "copper" | "cu" => Ok(ConductorPreset::Copper),
+            "aluminium" | "aluminum" | "al" => Ok(ConductorPreset::Aluminium),
+            "chrome" | "chromium" | "cr" => Ok(ConductorPreset::Chrome),
+            _ => Err(format!("Unknown metal {}", s)),
+        }
+    }
+}
+
+impl ConductorPreset {
+    pub fn ior(&self) -> ComplexIor {
+        let (eta, k) = match self {
+            ConductorPreset::Gold => ([0.143119, 0.374957, 1.44248], [3.98316, 2.38572, 1.60322]),
+            ConductorPreset::Silver => {
+                ([0.155265, 0.116723, 0.138342], [4.82835, 3.12225, 2.14696])
+            }
+            ConductorPreset::Copper => ([0.200438, 0.924033, 1.10221], [3.91295, 2.45285, 2.14219]),
+            ConductorPreset::Aluminium => {
+                ([1.65746, 0.880369, 0.521229], [9.22387, 6.26952, 4.837])
+            }
+            ConductorPreset::Chrome => ([3.1071, 3.1812, 2.3230], [3.3314, 3.3291, 3.1350]),
+        };
+        ComplexIor::new(
+            Vec3::new(eta[0], eta[1], eta[2]),
+            Vec3::new(k[0], k[1], k[2]),
+        )
+    }
+}
+
+#[test]
+fn test_fresnel_conductor() {
+    use crate::microfacet::fresnel_dielectric;
+
+    // 垂直入射时为 ((η-1)² + k²) / ((η+1)² + k²)
+    let (eta, k) = (0.2, 3.9);
+    let expected = ((eta - 1.) * (eta - 1.) + k * k) / ((eta + 1.) * (eta + 1.) + k * k);
+    assert!((fresnel_conductor(1., eta, k) - expected).abs() < 1e-5);
+    // 消光系数为零时退化为电介质
+    for cos_i in [1., 0.7, 0.3, 0.05] {
+        assert!((fresnel_conductor(cos_i, 1.5, 0.) - fresnel_dielectric(cos_i, 1.5)).abs() < 1e-4);
+    }
+    // 掠射时趋于全反射
+    for preset in [
+        ConductorPreset::Gold,
+        ConductorPreset::Silver,
+        ConductorPreset::Copper,
+        ConductorPreset::Aluminium,
+        ConductorPreset::Chrome,
+    ] {
+        let ior = preset.ior();
+        let (normal, grazing) = (ior.fresnel(1.), ior.fresnel(0.01));
+        assert!(grazing.x > 0.9 && grazing.y > 0.9 && grazing.z > 0.9);
+        assert!(normal.x <= grazing.x && normal.z <= grazing.z);
+    }
+    // 金偏黄，银近乎无色
+    let gold = ConductorPreset::Gold.ior().fresnel(1.);
+    assert!(gold.x > 0.9 && gold.z < 0.5);
+    let silver = ConductorPreset::Silver.ior().fresnel(1.);
+    assert!(silver.x > 0.9 && silver.z > 0.9);
+    assert_eq!("Aluminum".parse(), Ok(ConductorPreset::Aluminium));
+}
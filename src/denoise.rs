@@ -0,0 +1,290 @@
+//! 以辅助通道为引导的边缘保持降噪：A-Trous 小波滤波（Dammertz et al. 2010）
+//!
+//! 颜色先除以反照率得到光照，在光照上逐轮以间隔翻倍的 5×5 B 样条核滤波，
+//! 邻域像素的权重同时受颜色、法线与深度的差异约束，最后再乘回反照率，纹理与物体边缘不会被抹平。
+
+use rayon::prelude::*;
+
+use crate::aov::Aov;
+use crate::math::Float;
+use crate::render::Framebuffer;
+use crate::vec3::Vec3;
+
+/// 一维 B3 样条核
+const KERNEL: [Float; 5] = [1. / 16., 1. / 4., 3. / 8., 1. / 4., 1. / 16.];
+
+/// 反照率低于该值的分量不参与除法，以免放大噪声
+const MIN_ALBEDO: Float = 0.01;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Denoiser {
+    /// 滤波轮数，第 i 轮的采样间隔为 2^i 像素
+    pub iterations: usize,
+    /// 颜色差异的容忍度，作用于压缩到 [0, 1) 的光照，每轮减半
+    pub sigma_color: Float,
+    /// 法线夹角余弦的指数，越大越不容易跨越法线的突变
+    pub sigma_normal: Float,
+    /// 相对深度差异的容忍度
+    pub sigma_depth: Float,
+    /// 滤波前将比周围像素都亮的孤立亮点截断到邻域内的最大值，会一并压暗小光源与锐利的高光
+    pub clamp_fireflies: bool,
+}
+
+impl Default for Denoiser {
+    fn default() -> Self {
+        Denoiser {
+            iterations: 5,
+            sigma_color: 0.15,
+            sigma_normal: 64.,
+            sigma_depth: 0.1,
+            clamp_fireflies: false,
+        }
+    }
+}
+
+impl Denoiser {
+    pub fn with_iterations(mut self, iterations: usize) -> Denoiser {
+        self.iterations = iterations;
+        self
+    }
+
+    pub fn with_sigma_color(mut self, sigma_color: Float) -> Denoiser {
+        self.sigma_color = sigma_color;
+        self
+    }
+
+    pub fn with_clamp_fireflies(mut self, clamp_fireflies: bool) -> Denoiser {
+        self.clamp_fireflies = clamp_fireflies;
+        self
+    }
+
+    /// 降噪后的帧缓冲，辅助通道保持不变；缺少的引导通道不参与权重计算
+    pub fn denoise(&self, framebuffer: &Framebuffer) -> Framebuffer {
+        let (width, height) = (framebuffer.width, framebuffer.height);
+        let vectors = |values: &[Float]| -> Vec<Vec3> {
+            values
+                .chunks_exact(3)
+                .map(|c| Vec3::new(c[0], c[1], c[2]))
+                .collect()
+        };
+        let albedo = framebuffer.aov(Aov::Albedo).map(vectors);
+        // 物体边缘处的法线是平均值，归一化后再比较方向
+        let normals = framebuffer.aov(Aov::Normal).map(|values| {
+            vectors(values)
+                .into_iter()
+                .map(|n| {
+                    if n.length_squared() > 1e-12 {
+                        n.unit()
+                    } else {
+                        n
+                    }
+                })
+                .collect::<Vec<_>>()
+        });
+        let depth: Option<Vec<Float>> = framebuffer
+            .aov(Aov::Depth)
+            .map(|values| values.iter().step_by(3).copied().collect());
+
+        let demodulate = |a: &Vec3| a.apply(|c| if c > MIN_ALBEDO { c } else { 1. });
+        let mut color = vectors(&framebuffer.pixels);
+        if let Some(albedo) = &albedo {
+            for (c, a) in color.iter_mut().zip(albedo) {
+                *c = *c / demodulate(a);
+            }
+        }
+
+        // 比周围像素都亮得多的孤立亮点无法被颜色相近的邻居平滑，可以先截断到邻域内的最大值
+        if self.clamp_fireflies {
+            color = clamp_fireflies(&color, width, height);
+        }
+
+        let guides = Guides {
+            width,
+            height,
+            normals: normals.as_deref(),
+            depth: depth.as_deref(),
+        };
+        let mut sigma_color = self.sigma_color;
+        for i in 0..self.iterations {
+            let step = 1 << i;
+            let compressed: Vec<Vec3> = color
+                .iter()
+                .map(|c| c.apply(|v| v / (1. + v.max(0.))))
+                .collect();
+            let mut filtered = vec![Vec3::zeros(); width * height];
+            filtered
+                .par_chunks_mut(width.max(1))
+                .enumerate()
+                .for_each(|(y, row)| {
+                    for (x, out) in row.iter_mut().enumerate() {
+                        *out = self.filter_pixel(
+                            &guides,
+                            &color,
+                            &compressed,
+                            x,
+                            y,
+                            step,
+                            sigma_color,
+                        );
+                    }
+                });
+            color = filtered;
+            sigma_color *= 0.5;
+        }
+
+        if let Some(albedo) = &albedo {
+            for (c, a) in color.iter_mut().zip(albedo) {
+                *c = *c * demodulate(a);
+            }
+        }
+        let mut result = framebuffer.clone();
+        result.pixels = color.iter().flat_map(|c| [c.x, c.y, c.z]).collect();
+        result
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn filter_pixel(
+        &self,
+        guides: &Guides,
+        color: &[Vec3],
+        compressed: &[Vec3],
+        x: usize,
+        y: usize,
+        step: usize,
+        sigma_color: Float,
+    ) -> Vec3 {
+        let (width, height) = (guides.width, guides.height);
+        let p = y * width + x;
+        let mut sum = Vec3::zeros();
+        let mut weight_sum = 0.;
+        for (ky, hy) in KERNEL.iter().enumerate() {
+            let qy = y as isize + (ky as isize - 2) * step as isize;
+            if qy < 0 || qy >= height as isize {
+                continue;
+            }
+            for (kx, hx) in KERNEL.iter().enumerate() {
+                let qx = x as isize + (kx as isize - 2) * step as isize;
+                if qx < 0 || qx >= width as isize {
+                    continue;
+                }
+                let q = qy as usize * width + qx as usize;
+                let dc = (compressed[q] - compressed[p]).length_squared();
+                let mut weight = hx * hy * (-dc / (sigma_color * sigma_color)).exp();
+                if let Some(normals) = guides.normals {
+                    weight *= normals[p].dot(&normals[q]).max(0.).powf(self.sigma_normal);
+                }
+                if let Some(depth) = guides.depth {
+                    let scale = depth[p].abs().max(depth[q].abs()).max(1e-6);
+                    let dz = (depth[p] - depth[q]).abs() / scale;
+                    weight *= (-dz / (self.sigma_depth * step as Float)).exp();
+                }
+                sum = sum + color[q] * weight;
+                weight_sum += weight;
+            }
+        }
+        // 周围没有相似的像素时保留原值
+        if weight_sum > 0. {
+            sum / weight_sum
+        } else {
+            color[p]
+        }
+    }
+}
+
+/// 将每个像素的各分量截断到周围 8 个像素中的最大值，没有相邻像素时保留原值
+fn clamp_fireflies(color: &[Vec3], width: usize, height: usize) -> Vec<Vec3> {
+    let mut result = color.to_vec();
+    for y in 0..height {
+        for x in 0..width {
+            let mut max: Option<Vec3> = None;
+            for qy in y.saturating_sub(1)..(y + 2).min(height) {
+                for qx in x.saturating_sub(1)..(x + 2).min(width) {
+                    if (qx, qy) != (x, y) {
+                        let q = color[qy * width + qx];
+                        max = Some(max.map_or(q, |max| max.element_wise(Float::max, &q)));
+                    }
+                }
+            }
+            if let Some(max) = max {
+                let p = y * width + x;
+                result[p] = color[p].element_wise(Float::min, &max);
+            }
+        }
+    }
+    result
+}
+
+/// 滤波过程中不变的引导通道
+struct Guides<'a> {
+    width: usize,
+    height: usize,
+    normals: Option<&'a [Vec3]>,
+    depth: Option<&'a [Float]>,
+}
+
+#[test]
+fn test_denoise_preserves_edges() {
+    use crate::sampler::{RandomSampler, Sampler};
+
+    // 左右两半法线不同、亮度不同的平面，叠加噪声
+    let (width, height) = (32, 16);
+    let mut framebuffer = Framebuffer::new(width, height);
+    let mut normals = vec![];
+    let mut sampler = RandomSampler::new(1);
+    let clean = |x: usize| if x < width / 2 { 0.2 } else { 0.8 };
+    for y in 0..height {
+        for x in 0..width {
+            let noise = sampler.random_between(-0.15, 0.15);
+            framebuffer.set(x, y, Vec3::ones() * (clean(x) + noise));
+            let n = if x < width / 2 {
+                Vec3::new(1., 0., 0.)
+            } else {
+                Vec3::new(0., 0., 1.)
+            };
+            normals.extend_from_slice(&[n.x, n.y, n.z]);
+        }
+    }
+    framebuffer.aovs.push((Aov::Normal, normals));
+
+    let error = |fb: &Framebuffer| -> Float {
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| (fb.get(x, y).x - clean(x)).powi(2))
+            .sum::<Float>()
+            / (width * height) as Float
+    };
+    let denoised = Denoiser::default().denoise(&framebuffer);
+    assert!(error(&denoised) < error(&framebuffer) * 0.2);
+    // 边缘两侧的像素不会相互渗透
+    for y in 0..height {
+        assert!((denoised.get(width / 2 - 1, y).x - 0.2).abs() < 0.1);
+        assert!((denoised.get(width / 2, y).x - 0.8).abs() < 0.1);
+    }
+    assert_eq!(denoised.aovs, framebuffer.aovs);
+}
+
+#[test]
+fn test_clamp_fireflies() {
+    // 暗背景中央的孤立亮点，例如很小的光源
+    let mut framebuffer = Framebuffer::new(5, 5);
+    for y in 0..5 {
+        for x in 0..5 {
+            framebuffer.set(x, y, Vec3::ones() * 0.1);
+        }
+    }
+    framebuffer.set(2, 2, Vec3::ones() * 100.);
+    // 默认保留孤立的亮点，启用截断后压到邻域的亮度
+    assert!(Denoiser::default().denoise(&framebuffer).get(2, 2).x > 50.);
+    let clamped = Denoiser::default()
+        .with_clamp_fireflies(true)
+        .denoise(&framebuffer);
+    assert!(clamped.get(2, 2).x < 0.2);
+
+    // 只有一个像素时没有可供比较的邻居，保留原值
+    let mut single = Framebuffer::new(1, 1);
+    single.set(0, 0, Vec3::new(0.5, 1., 2.));
+    let denoised = Denoiser::default()
+        .with_clamp_fireflies(true)
+        .denoise(&single);
+    assert_eq!(denoised.get(0, 0), single.get(0, 0));
+}
@@ -0,0 +1,457 @@
+//! 重建滤波器与胶片：样本按滤波器权重累加到周围的像素上
+
+use std::str::FromStr;
+
+use crate::math::{floats, Float};
+use crate::tile::Tile;
+use crate::vec3::Vec3;
+
+/// 可分离的像素重建滤波器
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FilterKind {
+    /// 等权平均，半径 0.5 时每个样本只落在所在的像素中
+    Box,
+    /// 三角形滤波器，权重随距离线性衰减
+    Tent,
+    /// 减去半径处取值的截断 Gaussian，α = 2
+    Gaussian,
+    /// B = C = 1/3 的 Mitchell-Netravali 三次滤波器
+    Mitchell,
+    /// 以半径为窗口的 Lanczos 窗口化 sinc
+    Lanczos,
+}
+
+impl FromStr for FilterKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "box" => Ok(FilterKind::Box),
+            "tent" | "triangle" => Ok(FilterKind::Tent),
+            "gaussian" => Ok(FilterKind::Gaussian),
+            "mitchell" => Ok(FilterKind::Mitchell),
+            "lanczos" => Ok(FilterKind::Lanczos),
+            _ => Err(format!("Unknown filter {}", s)),
+        }
+    }
+}
+
+impl FilterKind {
+    /// 各滤波器的常用半径，单位为像素
+    pub fn default_radius(&self) -> Float {
+        match self {
+            FilterKind::Box => 0.5,
+            FilterKind::Tent => 1.,
+            FilterKind::Gaussian => 1.5,
+            FilterKind::Mitchell => 2.,
+            FilterKind::Lanczos => 3.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Filter {
+    pub kind: FilterKind,
+    pub radius: Float,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Filter::new(FilterKind::Box)
+    }
+}
+
+impl Filter {
+    pub fn new(kind: FilterKind) -> Filter {
+        Filter {
+            kind,
+            radius: kind.default_radius(),
+        }
+    }
+
+    pub fn with_radius(mut self, radius: Float) -> Filter {
+        self.radius = radius;
+        self
+    }
+
+    /// 样本相对像素中心偏移 (dx, dy) 处的权重，Mitchell 与 Lanczos 可能为负
+    pub fn evaluate(&self, dx: Float, dy: Float) -> Float {
+        self.evaluate_1d(dx) * self.evaluate_1d(dy)
+    }
+
+    fn evaluate_1d(&self, d: Float) -> Float {
+        let r = self.radius;
+        match self.kind {
+            // 左闭右开，恰好落在两像素分界上的样本只计入一侧
+            FilterKind::Box => {
+                if -r <= d && d < r {
+                    1.
+                } else {
+                    0.
+                }
+            }
+            FilterKind::Tent => (r - d.abs()).max(0.),
+            FilterKind::Gaussian => {
+                let gaussian = |x: Float| (-2. * x * x).exp();
+                (gaussian(d) - gaussian(r)).max(0.)
+            }
+            FilterKind::Mitchell => {
+                if d.abs() >= r {
+                    return 0.;
+                }
+                let x = (2. * d / r).abs();
+                let (b, c) = (1. / 3., 1. / 3.);
+                let value = if x > 1. {
+                    (-b - 6. * c) * x * x * x
+                        + (6. * b + 30. * c) * x * x
+                        + (-12. * b - 48. * c) * x
+                        + (8. * b + 24. * c)
+                } else {
+                    (12. - 9. * b - 6. * c) * x * x * x
+                        + (-18. + 12. * b + 6. * c) * x * x
+                        + (6. - 2. * b)
+                };
+                value / 6.
+            }
+            FilterKind::Lanczos => {
+                if d.abs() >= r {
+                    return 0.;
+                }
+                sinc(d) * sinc(d / r)
+            }
+        }
+    }
+
+    /// 一个样本最多影响到所在像素之外的行数或列数
+    fn margin(&self) -> usize {
+        (self.radius - 0.5).ceil().max(0.) as usize
+    }
+}
+
+fn sinc(x: Float) -> Float {
+    let x = x * floats::consts::PI;
+    if x.abs() < 1e-5 {
+        1.
+    } else {
+        x.sin() / x
+    }
+}
+
+/// 逐像素累加加权的颜色与权重，像素 (x, y) 的中心位于胶片坐标 (x + 0.5, y + 0.5)，y 轴向下
+///
+/// 每个样本可以携带多层取值，第 0 层为颜色，其余为辅助通道，各层共用同一份权重；
+/// 不能混合的层可以只保留像素内第一个样本的取值
+pub struct Film {
+    width: usize,
+    height: usize,
+    filter: Filter,
+    layers: usize,
+    /// 各层是否只保留第一个样本
+    first_sample: Vec<bool>,
+    sums: Vec<Vec3>,
+    weights: Vec<Float>,
+    /// 落在各像素内的样本数
+    counts: Vec<usize>,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize, filter: Filter) -> Film {
+        Film::with_layers(width, height, filter, 1)
+    }
+
+    /// 每个像素累加 `layers` 层取值的胶片
+    pub fn with_layers(width: usize, height: usize, filter: Filter, layers: usize) -> Film {
+        let layers = layers.max(1);
+        Film {
+            width,
+            height,
+            filter,
+            layers,
+            first_sample: vec![false; layers],
+            sums: vec![Vec3::zeros(); width * height * layers],
+            weights: vec![0.; width * height],
+            counts: vec![0; width * height],
+        }
+    }
+
+    /// 第 `layer` 层既不滤波也不取平均，只保留像素内序号最小的样本的取值，例如物体编号
+    pub fn with_first_sample(mut self, layer: usize) -> Film {
+        self.first_sample[layer] = true;
+        self
+    }
+
+    pub fn layers(&self) -> usize {
+        self.layers
+    }
+
+    pub fn filter(&self) -> &Filter {
+        &self.filter
+    }
+
+    /// 覆盖分块 `tile` 中的样本所能影响的全部像素的空白累加区域
+    pub fn tile(&self, tile: &Tile) -> FilmTile {
+        let margin = self.filter.margin();
+        let x0 = tile.x0.saturating_sub(margin);
+        let y0 = tile.y0.saturating_sub(margin);
+        let x1 = (tile.x1 + margin).min(self.width);
+        let y1 = (tile.y1 + margin).min(self.height);
+        let area = (x1 - x0) * (y1 - y0);
+        FilmTile {
+            x0,
+            y0,
+            x1,
+            y1,
+            filter: self.filter,
+            layers: self.layers,
+            first_sample: self.first_sample.clone(),
+            sums: vec![Vec3::zeros(); area * self.layers],
+            weights: vec![0.; area],
+            counts: vec![0; area],
+        }
+    }
+
+    /// 将分块累加到胶片上，按固定顺序合并的结果与线程数无关
+    pub fn merge(&mut self, tile: &FilmTile) {
+        let width = tile.x1 - tile.x0;
+        let layers = self.layers;
+        for (row, y) in (tile.y0..tile.y1).enumerate() {
+            for (column, x) in (tile.x0..tile.x1).enumerate() {
+                let (i, j) = (y * self.width + x, row * width + column);
+                for (layer, &first_sample) in self.first_sample.iter().enumerate() {
+                    let (sum, s) = (
+                        &mut self.sums[i * layers + layer],
+                        tile.sums[j * layers + layer],
+                    );
+                    if !first_sample {
+                        *sum = *sum + s;
+                    } else if self.counts[i] == 0 {
+                        // 分块中的第一个样本即为该像素序号最小的样本
+                        *sum = s;
+                    }
+                }
+                self.weights[i] += tile.weights[j];
+                self.counts[i] += tile.counts[j];
+            }
+        }
+    }
+
+    /// 像素 (x, y) 各层的加权和与权重之和
+    pub fn accumulation(&self, x: usize, y: usize) -> (&[Vec3], Float) {
+        let i = y * self.width + x;
+        (
+            &self.sums[i * self.layers..(i + 1) * self.layers],
+            self.weights[i],
+        )
+    }
+
+    /// 恢复像素 (x, y) 的累加结果，用于从检查点继续渲染；`count` 为该像素已采集的样本数
+    pub fn set_accumulation(
+        &mut self,
+        x: usize,
+        y: usize,
+        sums: &[Vec3],
+        weight: Float,
+        count: usize,
+    ) {
+        let i = y * self.width + x;
+        self.sums[i * self.layers..(i + 1) * self.layers].copy_from_slice(sums);
+        self.weights[i] = weight;
+        self.counts[i] = count;
+    }
+
+    /// 加权平均后的像素颜色，没有样本的像素为黑色
+    pub fn color(&self, x: usize, y: usize) -> Vec3 {
+        self.layer(x, y, 0)
+    }
+
+    /// 加权平均后像素第 `layer` 层的取值
+    pub fn layer(&self, x: usize, y: usize, layer: usize) -> Vec3 {
+        let i = y * self.width + x;
+        if self.first_sample[layer] {
+            self.sums[i * self.layers + layer]
+        } else if self.weights[i] == 0. {
+            Vec3::zeros()
+        } else {
+            self.sums[i * self.layers + layer] / self.weights[i]
+        }
+    }
+}
+
+/// 胶片中一块矩形区域的局部累加结果，供各线程独立写入
+pub struct FilmTile {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+    filter: Filter,
+    layers: usize,
+    first_sample: Vec<bool>,
+    sums: Vec<Vec3>,
+    weights: Vec<Float>,
+    counts: Vec<usize>,
+}
+
+impl FilmTile {
+    /// 在胶片坐标 (x, y) 处添加一个样本，`values` 为各层的取值，落在分块之外的部分被丢弃
+    pub fn add_sample(&mut self, x: Float, y: Float, values: &[Vec3]) {
+        let r = self.filter.radius;
+        let x0 = ((x - 0.5 - r).ceil().max(0.) as usize).max(self.x0);
+        let x1 = (((x - 0.5 + r).floor() + 1.).max(0.) as usize).min(self.x1);
+        let y0 = ((y - 0.5 - r).ceil().max(0.) as usize).max(self.y0);
+        let y1 = (((y - 0.5 + r).floor() + 1.).max(0.) as usize).min(self.y1);
+        for py in y0..y1 {
+            let wy = self.filter.evaluate_1d(y - (py as Float + 0.5));
+            if wy == 0. {
+                continue;
+            }
+            for px in x0..x1 {
+                let weight = wy * self.filter.evaluate_1d(x - (px as Float + 0.5));
+                if weight != 0. {
+                    let i = (py - self.y0) * (self.x1 - self.x0) + px - self.x0;
+                    for ((sum, value), _) in self.sums[i * self.layers..(i + 1) * self.layers]
+                        .iter_mut()
+                        .zip(values)
+                        .zip(&self.first_sample)
+                        .filter(|(_, &first_sample)| !first_sample)
+                    {
+                        *sum = *sum + *value * weight;
+                    }
+                    self.weights[i] += weight;
+                }
+            }
+        }
+        // 只保留第一个样本的层仅由样本所在的像素记录
+        let (px, py) = (x.floor(), y.floor());
+        if px >= self.x0 as Float
+            && px < self.x1 as Float
+            && py >= self.y0 as Float
+            && py < self.y1 as Float
+        {
+            let i = (py as usize - self.y0) * (self.x1 - self.x0) + px as usize - self.x0;
+            if self.counts[i] == 0 {
+                for ((sum, value), _) in self.sums[i * self.layers..(i + 1) * self.layers]
+                    .iter_mut()
+                    .zip(values)
+                    .zip(&self.first_sample)
+                    .filter(|(_, &first_sample)| first_sample)
+                {
+                    *sum = *value;
+                }
+            }
+            self.counts[i] += 1;
+        }
+    }
+}
+
+#[test]
+fn test_filters() {
+    for kind in [
+        FilterKind::Box,
+        FilterKind::Tent,
+        FilterKind::Gaussian,
+        FilterKind::Mitchell,
+        FilterKind::Lanczos,
+    ] {
+        let filter = Filter::new(kind);
+        let r = filter.radius;
+        assert!(filter.evaluate(0., 0.) > 0.);
+        assert_eq!(filter.evaluate(r + 0.1, 0.), 0.);
+        assert_eq!(filter.evaluate(0., -r - 0.1), 0.);
+        // 除左闭右开的盒形滤波器外均关于中心对称
+        if kind != FilterKind::Box {
+            assert_eq!(filter.evaluate(0.3, 0.7), filter.evaluate(-0.3, -0.7));
+        }
+    }
+    // Mitchell 滤波器积分为 1，且带有负的旁瓣
+    let mitchell = Filter::new(FilterKind::Mitchell);
+    let n = 1000;
+    let integral: Float = (0..n)
+        .map(|i| mitchell.evaluate_1d(-2. + 4. * (i as Float + 0.5) / n as Float) * 4. / n as Float)
+        .sum();
+    assert!((integral - 1.).abs() < 1e-3);
+    assert!(mitchell.evaluate_1d(1.5) < 0.);
+    // Lanczos 在非零整数处为零
+    let lanczos = Filter::new(FilterKind::Lanczos);
+    assert!(lanczos.evaluate_1d(1.).abs() < 1e-5);
+    assert!(lanczos.evaluate_1d(2.).abs() < 1e-5);
+}
+
+#[test]
+fn test_film_splat() {
+    // 半径 0.5 的盒形滤波器只把样本计入所在像素
+    let whole = Tile {
+        x0: 0,
+        y0: 0,
+        x1: 4,
+        y1: 4,
+    };
+    let mut film = Film::new(4, 4, Filter::default());
+    let mut tile = film.tile(&whole);
+    tile.add_sample(1.0, 1.999, &[Vec3::new(1., 2., 3.)]);
+    tile.add_sample(1.5, 1.5, &[Vec3::new(3., 2., 1.)]);
+    film.merge(&tile);
+    assert_eq!(film.color(1, 1), Vec3::new(2., 2., 2.));
+    assert_eq!(film.color(0, 1), Vec3::zeros());
+    assert_eq!(film.color(1, 2), Vec3::zeros());
+
+    // 帐篷滤波器把像素中心的样本分给上下左右的邻居
+    let mut film = Film::new(4, 4, Filter::new(FilterKind::Tent).with_radius(1.5));
+    let mut tile = film.tile(&Tile {
+        x0: 1,
+        y0: 1,
+        x1: 2,
+        y1: 2,
+    });
+    tile.add_sample(1.5, 1.5, &[Vec3::new(1., 1., 1.)]);
+    film.merge(&tile);
+    for (x, y) in [(0, 1), (2, 1), (1, 0), (1, 2), (2, 2)] {
+        assert_eq!(film.color(x, y), Vec3::new(1., 1., 1.));
+    }
+    assert_eq!(film.color(3, 1), Vec3::zeros());
+    assert_eq!(film.weights[4 + 1], 1.5 * 1.5);
+    assert_eq!(film.weights[4 + 2], 1.5 * 0.5);
+}
+
+#[test]
+fn test_film_layers() {
+    // 各层共用权重，辅助通道与颜色一样按滤波器加权平均
+    let mut film = Film::with_layers(2, 1, Filter::new(FilterKind::Tent), 2);
+    let mut tile = film.tile(&Tile {
+        x0: 0,
+        y0: 0,
+        x1: 2,
+        y1: 1,
+    });
+    tile.add_sample(0.5, 0.5, &[Vec3::ones(), Vec3::new(1., 0., 0.)]);
+    tile.add_sample(1.5, 0.5, &[Vec3::ones(), Vec3::new(0., 0., 1.)]);
+    film.merge(&tile);
+    assert_eq!(film.color(0, 0), Vec3::ones());
+    assert_eq!(film.layer(0, 0, 1), Vec3::new(1., 0., 0.));
+    let (sums, weight) = film.accumulation(1, 0);
+    assert_eq!((sums.len(), weight), (2, 1.));
+
+    // 只保留第一个样本的层不受滤波器旁瓣影响，也不会混合相邻物体的取值
+    let mut film =
+        Film::with_layers(3, 1, Filter::new(FilterKind::Mitchell), 2).with_first_sample(1);
+    let tile = Tile {
+        x0: 0,
+        y0: 0,
+        x1: 3,
+        y1: 1,
+    };
+    let mut first = film.tile(&tile);
+    first.add_sample(0.5, 0.5, &[Vec3::ones(), Vec3::new(1., 1., 1.)]);
+    first.add_sample(1.2, 0.5, &[Vec3::ones(), Vec3::new(2., 2., 2.)]);
+    first.add_sample(1.7, 0.5, &[Vec3::ones(), Vec3::new(3., 3., 3.)]);
+    film.merge(&first);
+    // 后续一轮的样本不会覆盖已有的取值
+    let mut second = film.tile(&tile);
+    second.add_sample(0.7, 0.5, &[Vec3::ones(), Vec3::new(4., 4., 4.)]);
+    second.add_sample(2.5, 0.5, &[Vec3::ones(), Vec3::new(5., 5., 5.)]);
+    film.merge(&second);
+    assert_eq!(film.layer(0, 0, 1), Vec3::ones());
+    assert_eq!(film.layer(1, 0, 1), Vec3::ones() * 2.);
+    assert_eq!(film.layer(2, 0, 1), Vec3::ones() * 5.);
+    assert_eq!(film.counts, vec![2, 2, 1]);
+    assert!(film.weights[2] != 0.);
+}
@@ -1,5 +1,7 @@
+use crate::aabb::Aabb;
 use crate::material::AnyMaterial;
 use crate::ray::Ray;
+use crate::sampler::Sampler;
 use crate::vec3::{Float, Vec3};
 
 use std::sync::Arc;
@@ -10,6 +12,18 @@ pub struct HitRecord {
     pub t: Float,
     pub front_face: bool,
     pub material: AnyMaterial,
+    /// 交点的表面参数坐标，三角形上为重心坐标或插值后的纹理坐标
+    pub u: Float,
+    pub v: Float,
+    /// 交点处沿 u 增大方向的切线 ∂p/∂u，不必归一化；没有参数化的表面为零向量
+    pub tangent: Vec3,
+    /// 交点坐标各分量的浮点误差上界
+    pub error: Float,
+    /// 交点所属物体在场景最外层物体列表中的序号
+    pub object_id: usize,
+    /// 到达交点时在吸收介质中走过的总距离，由 `spawn_ray` 传递给散射光线，
+    /// 因此光线在介质内部经其他物体反射或散射后仍累计同一段距离
+    pub medium_distance: Option<Float>,
 }
 
 impl HitRecord {
@@ -18,6 +32,8 @@ impl HitRecord {
         F: Fn(Vec3) -> Vec3,
     {
         let p = ray.at(t);
+        // o + t·d 的舍入误差不超过数个 ulp，以两项中的最大分量估计
+        let error = (max_abs(&ray.origin) + max_abs(&(ray.direction * t))) * 4. * Float::EPSILON;
         let outward_normal = f(p);
         let front_face = ray.direction.dot(&outward_normal) < 0.;
         HitRecord {
@@ -30,12 +46,75 @@ impl HitRecord {
                 -outward_normal.unit()
             },
             material,
+            u: 0.,
+            v: 0.,
+            tangent: Vec3::zeros(),
+            error,
+            object_id: 0,
+            medium_distance: ray.medium_distance_at(t),
         }
     }
+
+    /// 以法线为 z 轴的着色坐标系的另外两个轴，x 轴为切线在切平面上的投影，
+    /// 因此各向异性材质的方向随表面参数化连续变化；切线未知时任取一组正交基
+    pub fn shading_basis(&self) -> (Vec3, Vec3) {
+        let tangent = self.tangent - self.normal * self.normal.dot(&self.tangent);
+        if tangent.length_squared() > 1e-12 * self.tangent.length_squared().max(Float::MIN_POSITIVE)
+        {
+            let tangent = tangent.unit();
+            (tangent, self.normal.cross(&tangent))
+        } else {
+            self.normal.orthonormal_basis()
+        }
+    }
+
+    /// 将着色坐标系中的方向变换到世界坐标
+    pub fn from_local(&self, local: &Vec3) -> Vec3 {
+        let (s, t) = self.shading_basis();
+        s * local.x + t * local.y + self.normal * local.z
+    }
+
+    /// `from_local` 的逆变换
+    pub fn to_local(&self, world: &Vec3) -> Vec3 {
+        let (s, t) = self.shading_basis();
+        Vec3::new(s.dot(world), t.dot(world), self.normal.dot(world))
+    }
+
+    /// 自交点出发的光线，起点沿法线向 `direction` 一侧偏移误差上界，
+    /// 避免交点落在表面另一侧时散射光线与同一表面再次相交
+    pub fn spawn_ray(&self, direction: Vec3, time: Float) -> Ray {
+        let offset = if direction.dot(&self.normal) < 0. {
+            -self.error
+        } else {
+            self.error
+        };
+        let mut ray = Ray::new_at(self.p + self.normal * offset, direction, time);
+        ray.medium_distance = self.medium_distance;
+        ray
+    }
 }
 
-pub trait Hittable: Sync {
+/// 各分量绝对值的最大值
+pub(crate) fn max_abs(v: &Vec3) -> Float {
+    v.x.abs().max(v.y.abs()).max(v.z.abs())
+}
+
+pub trait Hittable: Send + Sync {
     fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord>;
+
+    /// 包围整个物体的轴对齐包围盒
+    fn bounding_box(&self) -> Aabb;
+
+    /// 以 `random_direction` 采样得到 `ray.direction` 的概率密度（立体角测度），
+    /// 只有可作为光源采样的物体需要实现
+    fn pdf_value(&self, _ray: &Ray) -> Float {
+        0.
+    }
+
+    /// 自 `origin` 指向物体上随机一点的方向，不必归一化
+    fn random_direction(&self, _origin: &Vec3, _time: Float, _sampler: &mut dyn Sampler) -> Vec3 {
+        Vec3::new(1., 0., 0.)
+    }
 }
 
 pub struct HittableList {
@@ -48,18 +127,66 @@ impl From<Vec<Arc<dyn Hittable>>> for HittableList {
     }
 }
 
+impl HittableList {
+    pub fn new() -> Self {
+        HittableList {
+            hittables: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, hittable: Arc<dyn Hittable>) {
+        self.hittables.push(hittable);
+    }
+
+    pub fn objects(&self) -> &[Arc<dyn Hittable>] {
+        &self.hittables
+    }
+
+    pub fn into_objects(self) -> Vec<Arc<dyn Hittable>> {
+        self.hittables
+    }
+}
+
+impl Default for HittableList {
+    fn default() -> Self {
+        HittableList::new()
+    }
+}
+
 unsafe impl Sync for HittableList {}
 
 impl Hittable for HittableList {
     fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
         let mut farthest = t_max;
         let mut nearst: Option<HitRecord> = None;
-        for obj in self.hittables.iter() {
-            if let Some(rec) = obj.hit(ray, t_min, farthest) {
+        for (i, obj) in self.hittables.iter().enumerate() {
+            if let Some(mut rec) = obj.hit(ray, t_min, farthest) {
                 farthest = rec.t;
+                // 嵌套的列表先写入内层的序号，由外层覆盖
+                rec.object_id = i;
                 nearst = Some(rec);
             };
         }
         nearst
     }
+
+    fn bounding_box(&self) -> Aabb {
+        self.hittables
+            .iter()
+            .fold(Aabb::empty(), |acc, obj| acc.union(&obj.bounding_box()))
+    }
+
+    /// 以等概率选取其中一个物体采样，概率密度为各物体概率密度的平均
+    fn pdf_value(&self, ray: &Ray) -> Float {
+        if self.hittables.is_empty() {
+            return 0.;
+        }
+        let sum: Float = self.hittables.iter().map(|obj| obj.pdf_value(ray)).sum();
+        sum / self.hittables.len() as Float
+    }
+
+    fn random_direction(&self, origin: &Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let index = sampler.random_index(self.hittables.len());
+        self.hittables[index].random_direction(origin, time, sampler)
+    }
 }
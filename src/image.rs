@@ -1,18 +1,93 @@
+use std::io::Write;
+use std::str::FromStr;
+
+use crate::math::Float;
+
 #[derive(Debug)]
 pub enum ImageError {
     IOError(std::io::Error),
     PNGEncodingError(png::EncodingError),
+    PNGDecodingError(png::DecodingError),
+    EXRError(exr::error::Error),
+    /// 无法由扩展名确定图像格式
+    UnsupportedFormat(String),
+}
+
+/// 输出图像格式，由文件扩展名决定
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ImageFormat {
+    /// 8 位 RGB，保存前需经过 Gamma 校正
+    Png,
+    /// OpenEXR，保存线性的高动态范围数据
+    Exr,
+    /// Portable Float Map，保存线性的 32 位浮点数据
+    Pfm,
+}
+
+impl ImageFormat {
+    pub fn from_path<P: AsRef<std::path::Path>>(path: P) -> Option<ImageFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "png" => Some(ImageFormat::Png),
+            "exr" => Some(ImageFormat::Exr),
+            "pfm" => Some(ImageFormat::Pfm),
+            _ => None,
+        }
+    }
+
+    /// 是否保存线性的浮点数据
+    pub fn is_hdr(&self) -> bool {
+        !matches!(self, ImageFormat::Png)
+    }
+}
+
+/// OpenEXR 通道的存储精度
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExrPrecision {
+    Half,
+    Float,
+}
+
+impl FromStr for ExrPrecision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "half" => Ok(ExrPrecision::Half),
+            "float" => Ok(ExrPrecision::Float),
+            _ => Err(format!("Unknown EXR precision {}", s)),
+        }
+    }
+}
+
+/// OpenEXR 的压缩方式，均为无损压缩
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ExrCompression {
+    None,
+    Zip,
+}
+
+impl FromStr for ExrCompression {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "none" => Ok(ExrCompression::None),
+            "zip" => Ok(ExrCompression::Zip),
+            _ => Err(format!("Unknown EXR compression {}", s)),
+        }
+    }
 }
 
 pub fn write_png(
     filename: &str,
     width: usize,
     height: usize,
-    pixels: &Vec<u8>,
+    pixels: &[u8],
 ) -> Result<bool, ImageError> {
     let path = std::path::Path::new(filename);
     let file = std::fs::File::create(path).map_err(ImageError::IOError)?;
-    let ref mut w = std::io::BufWriter::new(file);
+    let w = &mut std::io::BufWriter::new(file);
     let mut encoder = png::Encoder::new(w, width as u32, height as u32);
     encoder.set_color(png::ColorType::Rgb);
     encoder.set_depth(png::BitDepth::Eight);
@@ -24,3 +99,241 @@ pub fn write_png(
         .map_err(ImageError::PNGEncodingError)
         .map(|()| true)
 }
+
+/// 读取 PNG 图像，返回宽、高与逐行排列的 8 位 RGB 像素
+pub fn read_png<P: AsRef<std::path::Path>>(path: P) -> Result<(usize, usize, Vec<u8>), ImageError> {
+    let file = std::fs::File::open(path).map_err(ImageError::IOError)?;
+    let mut decoder = png::Decoder::new(std::io::BufReader::new(file));
+    // 调色板展开为 RGB，16 位通道截断为 8 位
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(ImageError::PNGDecodingError)?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader
+        .next_frame(&mut buffer)
+        .map_err(ImageError::PNGDecodingError)?;
+    let data = &buffer[..info.buffer_size()];
+    let pixels = match info.color_type {
+        png::ColorType::Rgb => data.to_vec(),
+        png::ColorType::Rgba => data
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect(),
+        png::ColorType::Grayscale => data.iter().flat_map(|&g| [g, g, g]).collect(),
+        png::ColorType::GrayscaleAlpha => data.chunks_exact(2).flat_map(|p| [p[0]; 3]).collect(),
+        png::ColorType::Indexed => unreachable!("palette is expanded by the decoder"),
+    };
+    Ok((info.width as usize, info.height as usize, pixels))
+}
+
+/// 将逐行排列的线性 RGB 浮点像素保存为 OpenEXR 图像
+#[allow(clippy::unnecessary_cast)] // 启用双精度时 Float 为 f64
+pub fn write_exr(
+    filename: &str,
+    width: usize,
+    height: usize,
+    pixels: &[Float],
+    precision: ExrPrecision,
+    compression: ExrCompression,
+) -> Result<(), ImageError> {
+    use exr::prelude::*;
+
+    let encoding = Encoding {
+        compression: match compression {
+            ExrCompression::None => Compression::Uncompressed,
+            ExrCompression::Zip => Compression::ZIP16,
+        },
+        blocks: Blocks::ScanLines,
+        line_order: LineOrder::Increasing,
+    };
+    let pixel = |position: Vec2<usize>| {
+        let i = (position.y() * width + position.x()) * 3;
+        (pixels[i] as f32, pixels[i + 1] as f32, pixels[i + 2] as f32)
+    };
+    let attributes = LayerAttributes::default();
+    let result = match precision {
+        ExrPrecision::Half => {
+            let channels = SpecificChannels::rgb(|position| {
+                let (r, g, b) = pixel(position);
+                (f16::from_f32(r), f16::from_f32(g), f16::from_f32(b))
+            });
+            Image::from_layer(Layer::new((width, height), attributes, encoding, channels))
+                .write()
+                .to_file(filename)
+        }
+        ExrPrecision::Float => {
+            let channels = SpecificChannels::rgb(pixel);
+            Image::from_layer(Layer::new((width, height), attributes, encoding, channels))
+                .write()
+                .to_file(filename)
+        }
+    };
+    result.map_err(ImageError::EXRError)
+}
+
+/// OpenEXR 单通道平面的取值，整数通道不受存储精度的影响
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExrSamples {
+    Float(Vec<Float>),
+    Uint(Vec<u32>),
+}
+
+/// 将若干逐行排列的单通道平面保存为一个 OpenEXR 图像，`channels` 为通道名与各像素的取值
+#[allow(clippy::unnecessary_cast)] // 启用双精度时 Float 为 f64
+pub fn write_exr_channels(
+    filename: &str,
+    width: usize,
+    height: usize,
+    channels: &[(String, ExrSamples)],
+    precision: ExrPrecision,
+    compression: ExrCompression,
+) -> Result<(), ImageError> {
+    use exr::prelude::*;
+
+    let encoding = Encoding {
+        compression: match compression {
+            ExrCompression::None => Compression::Uncompressed,
+            ExrCompression::Zip => Compression::ZIP16,
+        },
+        blocks: Blocks::ScanLines,
+        line_order: LineOrder::Increasing,
+    };
+    let list = channels
+        .iter()
+        .map(|(name, values)| {
+            let samples = match (values, precision) {
+                (ExrSamples::Uint(values), _) => FlatSamples::U32(values.clone()),
+                (ExrSamples::Float(values), ExrPrecision::Half) => {
+                    FlatSamples::F16(values.iter().map(|&v| f16::from_f32(v as f32)).collect())
+                }
+                (ExrSamples::Float(values), ExrPrecision::Float) => {
+                    FlatSamples::F32(values.iter().map(|&v| v as f32).collect())
+                }
+            };
+            AnyChannel::new(name.as_str(), samples)
+        })
+        .collect();
+    // 通道按名称排序后写入，读取时以名称区分
+    let channels = AnyChannels::sort(list);
+    let layer = Layer::new(
+        (width, height),
+        LayerAttributes::default(),
+        encoding,
+        channels,
+    );
+    Image::from_layer(layer)
+        .write()
+        .to_file(filename)
+        .map_err(ImageError::EXRError)
+}
+
+/// 将逐行排列的线性 RGB 浮点像素保存为 PFM 图像，文件中的像素行自下而上排列
+#[allow(clippy::unnecessary_cast)] // 启用双精度时 Float 为 f64
+pub fn write_pfm(
+    filename: &str,
+    width: usize,
+    height: usize,
+    pixels: &[Float],
+) -> Result<(), ImageError> {
+    let file = std::fs::File::create(filename).map_err(ImageError::IOError)?;
+    let mut w = std::io::BufWriter::new(file);
+    // 比例因子为负表示小端字节序
+    let mut data = format!("PF\n{} {}\n-1.0\n", width, height).into_bytes();
+    for row in pixels.chunks_exact(width * 3).rev() {
+        for &value in row {
+            data.extend_from_slice(&(value as f32).to_le_bytes());
+        }
+    }
+    w.write_all(&data)
+        .and_then(|()| w.flush())
+        .map_err(ImageError::IOError)
+}
+
+#[test]
+fn test_image_format() {
+    assert_eq!(ImageFormat::from_path("a/b.PNG"), Some(ImageFormat::Png));
+    assert_eq!(ImageFormat::from_path("out.exr"), Some(ImageFormat::Exr));
+    assert_eq!(ImageFormat::from_path("out.pfm"), Some(ImageFormat::Pfm));
+    assert_eq!(ImageFormat::from_path("out.jpg"), None);
+    assert_eq!(ImageFormat::from_path("out"), None);
+}
+
+#[test]
+fn test_write_hdr() {
+    // 2x1 图像，包含超出 [0, 1] 的高动态范围数值
+    let pixels = [0.25, 1.5, 1024., 0., 0.5, 7.75];
+
+    let path = std::env::temp_dir().join("toy-raytracer-rust-test-write.pfm");
+    write_pfm(path.to_str().unwrap(), 2, 1, &pixels).unwrap();
+    let data = std::fs::read(&path).unwrap();
+    let header = b"PF\n2 1\n-1.0\n";
+    assert_eq!(&data[..header.len()], header);
+    let values: Vec<f32> = data[header.len()..]
+        .chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect();
+    assert_eq!(values, [0.25, 1.5, 1024., 0., 0.5, 7.75]);
+
+    for precision in [ExrPrecision::Half, ExrPrecision::Float] {
+        for compression in [ExrCompression::None, ExrCompression::Zip] {
+            let path = std::env::temp_dir().join("toy-raytracer-rust-test-write.exr");
+            let filename = path.to_str().unwrap();
+            write_exr(filename, 2, 1, &pixels, precision, compression).unwrap();
+            let image = exr::prelude::read_first_rgba_layer_from_file(
+                filename,
+                |size, _| vec![0f32; size.area() * 3],
+                |buffer: &mut Vec<f32>, position, (r, g, b, _): (f32, f32, f32, f32)| {
+                    let i = position.x() * 3;
+                    buffer[i..i + 3].copy_from_slice(&[r, g, b]);
+                },
+            )
+            .unwrap();
+            // 以上数值均可由半精度浮点数精确表示
+            assert_eq!(image.layer_data.channel_data.pixels, values);
+        }
+    }
+}
+
+#[test]
+#[allow(clippy::unnecessary_cast)] // 启用双精度时 Float 为 f64
+fn test_write_exr_channels() {
+    use exr::prelude::*;
+
+    let path = std::env::temp_dir().join("toy-raytracer-rust-test-channels.exr");
+    let filename = path.to_str().unwrap();
+    let channels = vec![
+        ("R".to_string(), ExrSamples::Float(vec![0.25, 1.5])),
+        ("G".to_string(), ExrSamples::Float(vec![0., 2.])),
+        ("B".to_string(), ExrSamples::Float(vec![1., 4.])),
+        ("Z".to_string(), ExrSamples::Float(vec![3.5, 1024.])),
+        // 整数通道即使在半精度下也保存确切的取值
+        ("id".to_string(), ExrSamples::Uint(vec![0, 70001])),
+    ];
+    write_exr_channels(
+        filename,
+        2,
+        1,
+        &channels,
+        ExrPrecision::Half,
+        ExrCompression::Zip,
+    )
+    .unwrap();
+    let image = read_all_flat_layers_from_file(filename).unwrap();
+    let layer = &image.layer_data[0];
+    for (name, values) in &channels {
+        let channel = layer
+            .channel_data
+            .list
+            .iter()
+            .find(|c| c.name == *name.as_str())
+            .unwrap();
+        match (values, &channel.sample_data) {
+            (ExrSamples::Float(values), samples) => {
+                let read: Vec<f32> = samples.values_as_f32().collect();
+                let expected: Vec<f32> = values.iter().map(|&v| v as f32).collect();
+                assert_eq!(read, expected);
+            }
+            (ExrSamples::Uint(values), FlatSamples::U32(read)) => assert_eq!(read, values),
+            _ => panic!("unexpected sample type for channel {}", name),
+        }
+    }
+}
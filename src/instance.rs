@@ -0,0 +1,144 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{max_abs, HitRecord, Hittable};
+use crate::mat4::Mat4;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::{Float, Vec3};
+
+/// 经仿射变换放置的物体，多个实例可以共享同一物体
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    /// 物体空间到世界空间的变换
+    transform: Mat4,
+    inverse: Mat4,
+    /// 逆矩阵的转置，用于变换法线
+    normal_matrix: Mat4,
+    /// 线性部分行列式的绝对值
+    determinant: Float,
+    bounds: Aabb,
+}
+
+impl Instance {
+    /// `transform` 不可逆时 panic
+    pub fn new(object: Arc<dyn Hittable>, transform: Mat4) -> Instance {
+        let inverse = transform.inverse().expect("变换矩阵不可逆");
+        let bounds = transform_bounds(&transform, &object.bounding_box());
+        Instance {
+            object,
+            transform,
+            inverse,
+            normal_matrix: inverse.transpose(),
+            determinant: transform.linear_determinant().abs(),
+            bounds,
+        }
+    }
+
+    pub fn transform(&self) -> &Mat4 {
+        &self.transform
+    }
+
+    fn local_ray(&self, ray: &Ray) -> Ray {
+        Ray::new_at(
+            self.inverse.transform_point(&ray.origin),
+            self.inverse.transform_vector(&ray.direction),
+            ray.t,
+        )
+        .with_medium_sample(ray.medium_sample)
+    }
+}
+
+fn transform_bounds(transform: &Mat4, bounds: &Aabb) -> Aabb {
+    if bounds.extent().x < 0. {
+        return Aabb::empty();
+    }
+    (0..8).fold(Aabb::empty(), |acc, i| {
+        let corner = Vec3::new(
+            if i & 1 == 0 {
+                bounds.min.x
+            } else {
+                bounds.max.x
+            },
+            if i & 2 == 0 {
+                bounds.min.y
+            } else {
+                bounds.max.y
+            },
+            if i & 4 == 0 {
+                bounds.min.z
+            } else {
+                bounds.max.z
+            },
+        );
+        acc.include(&transform.transform_point(&corner))
+    })
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        // 方向不归一化，物体空间中的 t 与世界空间中的 t 相同
+        let local = self.local_ray(ray);
+        let mut rec = self.object.hit(&local, t_min, t_max)?;
+        rec.p = self.transform.transform_point(&rec.p);
+        // 物体空间中的误差经线性部分放大，再加上变换本身的舍入误差
+        rec.error =
+            rec.error * self.transform.linear_norm() + max_abs(&rec.p) * 4. * Float::EPSILON;
+        // (M⁻ᵀn)·(Md) = n·d，变换后法线仍与光线方向位于原来的一侧
+        rec.normal = self.normal_matrix.transform_vector(&rec.normal).unit();
+        rec.tangent = self.transform.transform_vector(&rec.tangent);
+        // 物体空间中的长度经过缩放，介质中的距离按世界空间重新计算
+        rec.medium_distance = ray.medium_distance_at(rec.t);
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bounds
+    }
+
+    /// 线性变换 A 将物体空间中的单位方向 ω 映射为世界空间中的方向 Aω/|Aω|，
+    /// 立体角的 Jacobian 为 |det A| / |Aω|³
+    fn pdf_value(&self, ray: &Ray) -> Float {
+        let local = self.local_ray(ray);
+        let pdf = self.object.pdf_value(&local);
+        if pdf == 0. {
+            return 0.;
+        }
+        let omega = local.direction.unit();
+        pdf * self.transform.transform_vector(&omega).length().powi(3) / self.determinant
+    }
+
+    fn random_direction(&self, origin: &Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let local_origin = self.inverse.transform_point(origin);
+        let direction = self.object.random_direction(&local_origin, time, sampler);
+        self.transform.transform_vector(&direction)
+    }
+}
+
+#[test]
+fn test_instance_hit() {
+    use crate::material::DummyMaterial;
+    use crate::sphere::Sphere;
+
+    let sphere = Arc::new(Sphere::new(Vec3::zeros(), 1., Arc::new(DummyMaterial {})));
+    let transform = Mat4::translate(Vec3::new(0., 0., -5.)) * Mat4::scale(Vec3::new(1., 1., 2.));
+    let instance = Instance::new(sphere, transform);
+
+    let ray = Ray::new(Vec3::zeros(), Vec3::new(0., 0., -1.));
+    let rec = instance.hit(&ray, 0.001, Float::MAX).unwrap();
+    assert!((rec.t - 3.).abs() < 1e-5);
+    assert!((rec.p - Vec3::new(0., 0., -3.)).length() < 1e-5);
+    assert!((rec.normal - Vec3::new(0., 0., 1.)).length() < 1e-5);
+    assert!(rec.front_face);
+
+    // 拉伸后的椭球在 x = 0.9 处的法线不再平行于 x 轴
+    let ray = Ray::new(Vec3::new(0.9, 0., 0.), Vec3::new(0., 0., -1.));
+    let rec = instance.hit(&ray, 0.001, Float::MAX).unwrap();
+    let local = rec.p - Vec3::new(0., 0., -5.);
+    let expected = Vec3::new(local.x, local.y, local.z / 4.).unit();
+    assert!((rec.normal - expected).length() < 1e-5);
+
+    let bounds = instance.bounding_box();
+    assert!((bounds.min - Vec3::new(-1., -1., -7.)).length() < 1e-5);
+    assert!((bounds.max - Vec3::new(1., 1., -3.)).length() < 1e-5);
+}
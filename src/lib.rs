@@ -1,10 +1,30 @@
+pub mod aabb;
+pub mod aov;
+pub mod bvh;
 pub mod camera;
+pub mod checkpoint;
 pub mod color;
+pub mod conductor;
+pub mod denoise;
+pub mod film;
 pub mod hittable;
 pub mod image;
+pub mod instance;
+pub mod mat4;
 pub mod material;
 pub mod math;
+pub mod medium;
+pub mod microfacet;
+pub mod obj;
 pub mod ray;
+pub mod render;
+pub mod sampler;
 pub mod scene;
+pub mod scene_file;
 pub mod sphere;
+pub mod texture;
+pub mod tile;
+pub mod tonemap;
+pub mod triangle;
 pub mod vec3;
+pub mod world;
@@ -1,17 +1,26 @@
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::{Arc, Mutex};
 use std::time::Instant;
 
 use clap::Parser;
-use fastrand;
 use git_version::git_version;
-use num_cpus;
 use pbr::ProgressBar;
-use rayon::prelude::*;
 
-use toy_raytracer_rust::image;
+use toy_raytracer_rust::aov::{Aov, AovSet};
+use toy_raytracer_rust::checkpoint::{hash_bytes, Checkpoint};
+use toy_raytracer_rust::denoise::Denoiser;
+use toy_raytracer_rust::film::{Filter, FilterKind};
+use toy_raytracer_rust::image::{ExrCompression, ExrPrecision, ImageFormat};
 use toy_raytracer_rust::math::Float;
+use toy_raytracer_rust::render::{
+    AdaptiveSampling, Framebuffer, OutputSettings, RenderSettings, Renderer,
+};
+use toy_raytracer_rust::sampler::{hash, SamplerKind};
 use toy_raytracer_rust::scene::{create_scene, Scene};
-use toy_raytracer_rust::vec3::{random, Vec3};
+use toy_raytracer_rust::scene_file::load_scene_file;
+use toy_raytracer_rust::tile::TileOrder;
+use toy_raytracer_rust::tonemap::{ToneMap, ToneMapping};
 
 #[derive(Parser, Debug)]
 #[clap(version=git_version!(), long_about=format!("Yet another toy implementation of https://raytracing.github.io, precision: {}", std::mem::size_of::<Float>() * 8))]
@@ -19,6 +28,10 @@ struct Opts {
     #[clap(short, long, default_value = "weekend")]
     scene: Scene,
 
+    /// 从 TOML 场景描述文件读取场景，指定时忽略 --scene
+    #[clap(long)]
+    scene_file: Option<String>,
+
     #[clap(long, default_value = "0")]
     seed: u64,
 
@@ -31,21 +44,112 @@ struct Opts {
     #[clap(short, long, default_value = "0")]
     num_threads: usize,
 
+    /// 启用自适应采样时为平均每像素的样本预算
     #[clap(short, long, default_value = "500")]
     samples_per_pixel: usize,
 
+    /// 像素重建滤波器：box、tent、gaussian、mitchell 或 lanczos
+    #[clap(long, default_value = "box")]
+    filter: FilterKind,
+
+    /// 滤波器半径，单位为像素，默认取各滤波器的常用值
+    #[clap(long)]
+    filter_radius: Option<Float>,
+
+    /// 分块边长，单位为像素
+    #[clap(long, default_value = "16")]
+    tile_size: usize,
+
+    /// 分块的渲染顺序：scanline、spiral 或 hilbert
+    #[clap(long, default_value = "spiral")]
+    tile_order: TileOrder,
+
+    /// 渐进式渲染，每为所有像素采集 N 个样本就重写一次输出文件
+    #[clap(long)]
+    preview_every: Option<usize>,
+
+    /// 每一轮结束后将累加状态保存到该检查点文件
+    #[clap(long)]
+    checkpoint: Option<String>,
+
+    /// 保存检查点时每一轮为所有像素采集的样本数，指定 --preview-every 时以其为准
+    #[clap(long, default_value = "16")]
+    checkpoint_every: usize,
+
+    /// 从 --checkpoint 指定的检查点继续渲染，场景或参数改变时拒绝恢复
+    #[clap(long, requires = "checkpoint")]
+    resume: bool,
+
+    /// 启用自适应采样，像素亮度 95% 置信区间的相对半宽低于该值时停止采样
+    #[clap(long)]
+    adaptive_threshold: Option<Float>,
+
+    /// 自适应采样时每个像素至少采集的样本数
+    #[clap(long, default_value = "16")]
+    min_samples: usize,
+
+    /// 将每个像素的样本数热力图保存到该文件
+    #[clap(long)]
+    heatmap: Option<String>,
+
+    /// 输出的辅助通道，以逗号分隔：albedo、normal、depth、position 或 id
+    #[clap(long)]
+    aov: Option<AovSet>,
+
+    /// 保存前以反照率、法线与深度为引导降噪，样本数较少的预览也能看清
+    #[clap(long)]
+    denoise: bool,
+
+    /// 降噪前将孤立的亮点截断到相邻像素的亮度，会一并压暗很小的光源与锐利的高光
+    #[clap(long)]
+    clamp_fireflies: bool,
+
+    /// 采样器：random、stratified、halton 或 sobol
+    #[clap(long, default_value = "random")]
+    sampler: SamplerKind,
+
     #[clap(short, long, default_value = "50")]
     max_depth: i32,
 
+    /// 输出文件，格式由扩展名决定：png、exr 或 pfm
     #[clap(short, long, default_value = "output.png")]
     output: String,
+
+    /// EXR 通道精度：half 或 float
+    #[clap(long, default_value = "float")]
+    exr_precision: ExrPrecision,
+
+    /// EXR 压缩方式：none 或 zip
+    #[clap(long, default_value = "zip")]
+    exr_compression: ExrCompression,
+
+    /// PNG 输出的色调映射：clamp、reinhard、extended-reinhard、hable 或 aces
+    #[clap(long, default_value = "clamp")]
+    tone_map: ToneMap,
+
+    /// PNG 输出的曝光补偿，单位为档
+    #[clap(long, default_value = "0", allow_negative_numbers = true)]
+    exposure: Float,
+
+    /// extended-reinhard 映射为纯白的亮度
+    #[clap(long, default_value = "4")]
+    white_point: Float,
+
+    /// 禁用层次包围盒，逐个物体求交
+    #[clap(long)]
+    no_bvh: bool,
 }
 
 fn main() {
     // 解析命令行参数
     let opts: Opts = Opts::parse();
 
-    fastrand::seed(opts.seed);
+    for output in std::iter::once(&opts.output).chain(&opts.heatmap) {
+        if ImageFormat::from_path(output).is_none() {
+            println!("Error: unsupported output format: {}", output);
+            std::process::exit(1);
+        }
+    }
 
     // 确定图像大小
     let aspect_ratio = opts.aspect_ratio;
@@ -54,7 +158,21 @@ fn main() {
     let samples_per_pixel = opts.samples_per_pixel;
 
     // 创建场景
-    let (camera, world, ray_color) = create_scene(opts.scene, aspect_ratio);
+    // 场景描述文件及其引用的文件，内容变化时不能从检查点继续
+    let (camera, world, ray_color, scene_files) = match &opts.scene_file {
+        Some(path) => match load_scene_file(path, aspect_ratio, !opts.no_bvh) {
+            Ok(scene) => scene,
+            Err(e) => {
+                println!("Error: failed to load {}: {}", path, e);
+                std::process::exit(1);
+            }
+        },
+        None => {
+            let (camera, world, ray_color) =
+                create_scene(opts.scene, aspect_ratio, !opts.no_bvh, opts.seed);
+            (camera, world, ray_color, vec![])
+        }
+    };
 
     // 配置 Rayon
     if 0 < opts.num_threads && opts.num_threads <= num_cpus::get() {
@@ -72,37 +190,91 @@ fn main() {
     );
     let start_time = Instant::now();
     let pbar = Arc::new(Mutex::new(ProgressBar::new(image_height as u64)));
-    let pixels = (0..image_height)
-        .into_par_iter()
-        .map(|y| {
-            fastrand::seed(opts.seed + y as u64);
-            let pbar_lock = pbar.clone();
-            pbar_lock.lock().unwrap().inc();
-            (0..image_width)
-                .into_iter()
-                .map(|x| {
-                    let mut color = Vec3::zeros();
-                    for _ in 0..samples_per_pixel {
-                        let u = (x as Float + random()) / (image_width - 1) as Float;
-                        let v = 1. - (y as Float + random()) / (image_height - 1) as Float;
-                        let ray = camera.ray(u, v);
-                        color = color + ray_color(&ray, &world, opts.max_depth);
-                    }
-                    // 计算平均色彩并应用 Gamma 校正
-                    (color / samples_per_pixel as Float)
-                        .apply(Float::sqrt)
-                        .into_color()
-                        .as_vec_u8()
-                })
-                .flatten()
-                .collect::<Vec<u8>>()
+    let mut settings = RenderSettings::new(image_width, image_height)
+        .with_samples_per_pixel(samples_per_pixel)
+        .with_max_depth(opts.max_depth)
+        .with_seed(opts.seed)
+        .with_sampler(opts.sampler)
+        .with_filter(match opts.filter_radius {
+            Some(radius) => Filter::new(opts.filter).with_radius(radius),
+            None => Filter::new(opts.filter),
         })
-        .flatten()
-        .collect::<Vec<u8>>();
+        .with_tile_size(opts.tile_size)
+        .with_tile_order(opts.tile_order)
+        .with_aovs(render_aovs(&opts));
+    if let Some(samples_per_pass) = opts.preview_every {
+        settings = settings.with_samples_per_pass(samples_per_pass);
+    } else if opts.checkpoint.is_some() {
+        settings = settings.with_samples_per_pass(opts.checkpoint_every);
+    }
+    if let Some(threshold) = opts.adaptive_threshold {
+        settings = settings
+            .with_adaptive(AdaptiveSampling::new(threshold).with_min_samples(opts.min_samples));
+    }
+    let output = OutputSettings {
+        tone_mapping: ToneMapping::new(opts.tone_map)
+            .with_exposure(opts.exposure)
+            .with_white_point(opts.white_point),
+        exr_precision: opts.exr_precision,
+        exr_compression: opts.exr_compression,
+    };
+    let pbar_lock = pbar.clone();
+    // 各线程报告进度的先后不定，只前进不后退
+    let shown = AtomicUsize::new(0);
+    let mut renderer =
+        Renderer::new(camera, world, ray_color, settings).with_progress(move |done, total| {
+            let mut pbar = pbar_lock.lock().unwrap();
+            pbar.total = total as u64;
+            if shown.fetch_max(done, Ordering::Relaxed) < done {
+                pbar.set(done as u64);
+            }
+        });
+    if opts.preview_every.is_some() {
+        // 每一轮结束后覆盖输出文件，随时可以查看并提前终止
+        let filename = opts.output.clone();
+        let (denoiser, aovs) = (denoiser(&opts), opts.aov.unwrap_or_default());
+        renderer = renderer.with_preview(move |framebuffer| {
+            let framebuffer = postprocess(framebuffer, denoiser, aovs);
+            if let Err(e) = framebuffer.save(&filename, &output) {
+                println!("Error: {:?}", e);
+            }
+        });
+    }
+    if let Some(path) = &opts.checkpoint {
+        renderer = renderer.with_scene_hash(scene_hash(&opts, &scene_files));
+        if opts.resume {
+            let resumed = Checkpoint::load(path).and_then(|c| renderer.resume_from(c));
+            renderer = match resumed {
+                Ok(renderer) => renderer,
+                Err(e) => {
+                    println!("Error: failed to resume from {}: {}", path, e);
+                    std::process::exit(1);
+                }
+            };
+        }
+        let filename = path.clone();
+        renderer = renderer.with_checkpoint(move |checkpoint| {
+            if let Err(e) = checkpoint.save(&filename) {
+                println!("Error: failed to save checkpoint: {}", e);
+            }
+        });
+    }
+    let framebuffer = renderer.render().expect("rendering is never cancelled");
+    let framebuffer = postprocess(&framebuffer, denoiser(&opts), opts.aov.unwrap_or_default());
 
     // 保存渲染结果
-    match image::write_png(opts.output.as_str(), image_width, image_height, &pixels) {
-        Ok(_) => {
+    if let Some(heatmap) = &opts.heatmap {
+        // 热力图的颜色已经确定，不做曝光补偿与色调映射
+        let heatmap_output = OutputSettings {
+            tone_mapping: ToneMapping::default(),
+            ..output
+        };
+        if let Err(e) = framebuffer.sample_heatmap().save(heatmap, &heatmap_output) {
+            println!("Error: {:?}", e);
+        }
+    }
+    match framebuffer.save(&opts.output, &output) {
+        Ok(()) => {
             let time_cost = start_time.elapsed().as_millis() as Float / 1000.;
             let message = format!(
                 "Rendered in {:.3} secs, saved to {}",
@@ -113,3 +285,44 @@ fn main() {
         Err(e) => println!("Error: {:?}", e),
     }
 }
+
+/// 降噪需要的引导通道即使未要求输出也一并渲染
+fn render_aovs(opts: &Opts) -> AovSet {
+    let aovs = opts.aov.unwrap_or_default();
+    if opts.denoise {
+        aovs.with(Aov::Albedo).with(Aov::Normal).with(Aov::Depth)
+    } else {
+        aovs
+    }
+}
+
+/// 指定 `--denoise` 时使用的降噪器
+fn denoiser(opts: &Opts) -> Option<Denoiser> {
+    opts.denoise
+        .then(|| Denoiser::default().with_clamp_fireflies(opts.clamp_fireflies))
+}
+
+/// 按需降噪，并去掉仅用于引导降噪的辅助通道
+fn postprocess(framebuffer: &Framebuffer, denoiser: Option<Denoiser>, aovs: AovSet) -> Framebuffer {
+    let mut result = match denoiser {
+        Some(denoiser) => denoiser.denoise(framebuffer),
+        None => framebuffer.clone(),
+    };
+    result.aovs.retain(|(aov, _)| aovs.contains(*aov));
+    result
+}
+
+/// 内置场景由名称、种子与宽高比决定，场景描述文件由它及其引用的 OBJ、MTL 与贴图的内容以及宽高比决定
+#[allow(clippy::unnecessary_cast)] // 启用双精度时 Float 为 f64
+fn scene_hash(opts: &Opts, scene_files: &[PathBuf]) -> u64 {
+    let scene = if opts.scene_file.is_some() {
+        let contents: Vec<u64> = scene_files
+            .iter()
+            .map(|path| hash_bytes(&std::fs::read(path).unwrap_or_default()))
+            .collect();
+        hash(&contents)
+    } else {
+        hash(&[opts.scene as u64, opts.seed])
+    };
+    hash(&[scene, opts.aspect_ratio.to_bits() as u64])
+}
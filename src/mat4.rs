@@ -0,0 +1,195 @@
+use std::ops::Mul;
+
+use crate::math::Float;
+use crate::vec3::Vec3;
+
+/// 4x4 仿射变换矩阵，按行存储，作用于列向量
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mat4 {
+    pub m: [[Float; 4]; 4],
+}
+
+impl Mat4 {
+    pub fn new(m: [[Float; 4]; 4]) -> Mat4 {
+        Mat4 { m }
+    }
+
+    pub fn identity() -> Mat4 {
+        Mat4::new([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn translate(offset: Vec3) -> Mat4 {
+        Mat4::new([
+            [1., 0., 0., offset.x],
+            [0., 1., 0., offset.y],
+            [0., 0., 1., offset.z],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn scale(factor: Vec3) -> Mat4 {
+        Mat4::new([
+            [factor.x, 0., 0., 0.],
+            [0., factor.y, 0., 0.],
+            [0., 0., factor.z, 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    /// 绕过原点的 `axis` 轴逆时针旋转 `degrees` 度（Rodrigues 公式）
+    pub fn rotate(axis: Vec3, degrees: Float) -> Mat4 {
+        let a = axis.unit();
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        let k = 1. - cos;
+        Mat4::new([
+            [
+                a.x * a.x * k + cos,
+                a.x * a.y * k - a.z * sin,
+                a.x * a.z * k + a.y * sin,
+                0.,
+            ],
+            [
+                a.y * a.x * k + a.z * sin,
+                a.y * a.y * k + cos,
+                a.y * a.z * k - a.x * sin,
+                0.,
+            ],
+            [
+                a.z * a.x * k - a.y * sin,
+                a.z * a.y * k + a.x * sin,
+                a.z * a.z * k + cos,
+                0.,
+            ],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn transpose(&self) -> Mat4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Mat4::new(m)
+    }
+
+    /// 左上角 3x3 线性部分的行列式
+    pub fn linear_determinant(&self) -> Float {
+        let m = &self.m;
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    }
+
+    /// 线性部分的无穷范数，即各行元素绝对值之和的最大值
+    pub fn linear_norm(&self) -> Float {
+        self.m[..3]
+            .iter()
+            .map(|row| row[..3].iter().map(|x| x.abs()).sum::<Float>())
+            .fold(0., Float::max)
+    }
+
+    /// 以列主元 Gauss-Jordan 消元求逆，矩阵奇异时返回 `None`
+    pub fn inverse(&self) -> Option<Mat4> {
+        let mut a = self.m;
+        let mut inv = Mat4::identity().m;
+        for col in 0..4 {
+            let pivot = (col..4)
+                .max_by(|&i, &j| a[i][col].abs().total_cmp(&a[j][col].abs()))
+                .unwrap();
+            if a[pivot][col] == 0. {
+                return None;
+            }
+            a.swap(col, pivot);
+            inv.swap(col, pivot);
+            let scale = 1. / a[col][col];
+            for j in 0..4 {
+                a[col][j] *= scale;
+                inv[col][j] *= scale;
+            }
+            for row in 0..4 {
+                if row != col {
+                    let factor = a[row][col];
+                    for j in 0..4 {
+                        a[row][j] -= factor * a[col][j];
+                        inv[row][j] -= factor * inv[col][j];
+                    }
+                }
+            }
+        }
+        Some(Mat4::new(inv))
+    }
+
+    /// 变换点，包含平移
+    pub fn transform_point(&self, p: &Vec3) -> Vec3 {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1. {
+            Vec3::new(x, y, z)
+        } else {
+            Vec3::new(x, y, z) / w
+        }
+    }
+
+    /// 变换方向，忽略平移
+    pub fn transform_vector(&self, v: &Vec3) -> Vec3 {
+        let m = &self.m;
+        Vec3::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
+}
+
+impl Default for Mat4 {
+    fn default() -> Self {
+        Mat4::identity()
+    }
+}
+
+impl Mul for Mat4 {
+    type Output = Mat4;
+
+    /// `a * b` 先施加 `b` 再施加 `a`
+    fn mul(self, other: Mat4) -> Mat4 {
+        let mut m = [[0.; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * other.m[k][j]).sum();
+            }
+        }
+        Mat4::new(m)
+    }
+}
+
+#[test]
+fn test_mat4_inverse() {
+    let m = Mat4::translate(Vec3::new(1., -2., 3.))
+        * Mat4::rotate(Vec3::new(1., 1., 0.), 30.)
+        * Mat4::scale(Vec3::new(2., 0.5, 3.));
+    let product = m * m.inverse().unwrap();
+    for i in 0..4 {
+        for j in 0..4 {
+            let expected = if i == j { 1. } else { 0. };
+            assert!((product.m[i][j] - expected).abs() < 1e-5);
+        }
+    }
+    assert!(Mat4::scale(Vec3::new(1., 0., 1.)).inverse().is_none());
+}
+
+#[test]
+fn test_mat4_rotate() {
+    let m = Mat4::rotate(Vec3::new(0., 1., 0.), 90.);
+    let p = m.transform_point(&Vec3::new(1., 0., 0.));
+    assert!((p - Vec3::new(0., 0., -1.)).length() < 1e-6);
+}
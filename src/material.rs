@@ -1,8 +1,14 @@
 use std::sync::Arc;
 
+use crate::conductor::ComplexIor;
 use crate::hittable::HitRecord;
-use crate::math::{random, Float};
+use crate::math::floats::consts::{FRAC_1_PI, PI};
+use crate::math::Float;
+use crate::microfacet::{self, fresnel_dielectric, fresnel_schlick, Distribution, Microfacet};
 use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::texture::{AnyTexture, SolidColor};
+use crate::tonemap::luminance;
 use crate::vec3::Vec3;
 
 pub struct Scatter {
@@ -10,78 +16,155 @@ pub struct Scatter {
     pub attenuation: Vec3,
     /// 散射光线
     pub ray: Ray,
+    /// 采样散射方向的概率密度（立体角测度），镜面反射与折射等 delta 分布为 `None`
+    pub pdf: Option<Float>,
 }
 
-pub trait Material: Sync {
-    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<Scatter>;
+pub trait Material: Send + Sync {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter>;
+
+    /// BSDF 与余弦项之积，即自 `direction` 入射的光对出射方向 `-ray.direction` 的贡献；
+    /// delta 分布的材质无法对给定方向求值，返回零
+    fn eval(&self, _ray: &Ray, _rec: &HitRecord, _direction: &Vec3) -> Vec3 {
+        Vec3::zeros()
+    }
+
+    /// `scatter` 采样得到 `direction` 的概率密度，与 `Scatter::pdf` 一致
+    fn pdf(&self, _ray: &Ray, _rec: &HitRecord, _direction: &Vec3) -> Float {
+        0.
+    }
+
+    /// 自发光的辐射亮度，不发光的材质返回零
+    fn emitted(&self, _rec: &HitRecord) -> Vec3 {
+        Vec3::zeros()
+    }
+
+    /// 表面的反照率，用于输出辅助通道；镜面与透明材质为其色调
+    fn albedo(&self, _rec: &HitRecord) -> Vec3 {
+        Vec3::zeros()
+    }
 }
 
 pub type AnyMaterial = Arc<dyn Material>;
 
+/// 在单位球面上均匀采样方向的概率密度
+const UNIFORM_SPHERE_PDF: Float = 0.25 * FRAC_1_PI;
+
 /// 博朗材质
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Lambertian {
     /// 反照率
-    pub albedo: Vec3,
+    pub albedo: AnyTexture,
 }
 
 impl Lambertian {
     pub fn new(r: Float, g: Float, b: Float) -> Lambertian {
-        Lambertian {
-            albedo: Vec3::new(r, g, b),
-        }
+        Lambertian::textured(Arc::new(SolidColor::new(r, g, b)))
+    }
+
+    pub fn textured(albedo: AnyTexture) -> Lambertian {
+        Lambertian { albedo }
     }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _: &Ray, rec: &HitRecord) -> Option<Scatter> {
-        let direction = rec.normal + Vec3::random_unit_vector();
+    fn scatter(&self, ray: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        // 法线加单位球面上的随机向量，方向按余弦分布
+        let direction = rec.normal + Vec3::random_unit_vector(sampler);
         Some(Scatter {
-            attenuation: self.albedo,
-            ray: Ray::new(rec.p, direction),
+            attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
+            ray: rec.spawn_ray(direction, ray.t),
+            pdf: Some(self.pdf(ray, rec, &direction)),
         })
     }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, &rec.p) * self.pdf(ray, rec, direction)
+    }
+
+    fn pdf(&self, _ray: &Ray, rec: &HitRecord, direction: &Vec3) -> Float {
+        rec.normal.dot(&direction.unit()).max(0.) * FRAC_1_PI
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
 }
 
 /// 金属材质
-#[derive(Debug, Clone)]
+#[derive(Clone)]
 pub struct Metal {
     /// 反照率
-    pub albedo: Vec3,
+    pub albedo: AnyTexture,
     pub fuzz: Float,
 }
 
 impl Metal {
     pub fn new(r: Float, g: Float, b: Float, fuzz: Float) -> Metal {
+        Metal::textured(Arc::new(SolidColor::new(r, g, b)), fuzz)
+    }
+
+    pub fn textured(albedo: AnyTexture, fuzz: Float) -> Metal {
         Metal {
-            albedo: Vec3::new(r, g, b),
-            fuzz: fuzz.min(1.0).max(0.0),
+            albedo,
+            fuzz: fuzz.clamp(0.0, 1.0),
         }
     }
 }
 
 impl Material for Metal {
-    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<Scatter> {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
         let direction = ray.direction.unit().reflect(&rec.normal);
         if direction.dot(&rec.normal) > 0. {
             Some(Scatter {
-                attenuation: self.albedo,
-                ray: Ray::new(rec.p, direction + Vec3::random_in_unit_sphere() * self.fuzz),
+                attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
+                ray: rec.spawn_ray(
+                    direction + Vec3::random_in_unit_sphere(sampler) * self.fuzz,
+                    ray.t,
+                ),
+                // 模糊反射的方向分布没有简单的解析形式，与镜面反射同样视作 delta 分布
+                pdf: None,
             })
         } else {
             None
         }
     }
+
+    fn albedo(&self, rec: &HitRecord) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct Dielectric {
     ref_idx: Float,
+    /// 内部介质各通道的吸收系数，光在其中传播距离 d 后衰减为 exp(-σ·d)，为零时完全透明
+    absorption: Vec3,
 }
 
 impl Dielectric {
     pub fn new(ri: Float) -> Self {
-        Dielectric { ref_idx: ri }
+        Dielectric::absorbing(ri, Vec3::zeros())
+    }
+
+    /// 有色玻璃或液体
+    pub fn absorbing(ri: Float, absorption: Vec3) -> Self {
+        Dielectric {
+            ref_idx: ri,
+            absorption,
+        }
+    }
+
+    /// 穿过表面的散射光线：进入时开始累计介质中的距离，离开时按累计的总距离衰减；
+    /// 在内部反射或经其他物体散射时距离由 `HitRecord::spawn_ray` 继续传递
+    fn cross(&self, rec: &HitRecord, ray: &mut Ray) -> Vec3 {
+        if rec.front_face {
+            ray.medium_distance = Some(0.);
+            Vec3::ones()
+        } else {
+            ray.medium_distance = None;
+            (self.absorption * -rec.medium_distance.unwrap_or(0.)).apply(Float::exp)
+        }
     }
 
     fn schlick(cosine: Float, ref_idx: Float) -> Float {
@@ -91,7 +174,7 @@ impl Dielectric {
 }
 
 impl Material for Dielectric {
-    fn scatter(&self, ray: &Ray, rec: &HitRecord) -> Option<Scatter> {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
         let etai_over_etat = if rec.front_face {
             1. / self.ref_idx
         } else {
@@ -105,24 +188,739 @@ impl Material for Dielectric {
             unit_direction.reflect(&rec.normal)
         } else {
             // 否则由菲涅尔-施里克近似估计折射光线
-            if random() < Self::schlick(cos_theta, etai_over_etat) {
+            if sampler.random() < Self::schlick(cos_theta, etai_over_etat) {
                 unit_direction.reflect(&rec.normal)
             } else {
                 unit_direction.refract(&rec.normal, etai_over_etat)
             }
         };
+        let mut scattered = rec.spawn_ray(direction, ray.t);
+        let attenuation = if self.absorption != Vec3::zeros() && direction.dot(&rec.normal) < 0. {
+            self.cross(rec, &mut scattered)
+        } else {
+            Vec3::ones()
+        };
+        Some(Scatter {
+            attenuation,
+            ray: scattered,
+            pdf: None,
+        })
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Vec3 {
+        Vec3::ones()
+    }
+}
+
+/// 导体的菲涅尔项
+#[derive(Clone)]
+pub enum ConductorFresnel {
+    /// 以纹理颜色为法向反射率的施里克近似
+    Schlick(AnyTexture),
+    /// 由复折射率求得的完整菲涅尔方程
+    Complex(ComplexIor),
+}
+
+impl ConductorFresnel {
+    fn value(&self, rec: &HitRecord, cos_i: Float) -> Vec3 {
+        match self {
+            ConductorFresnel::Schlick(albedo) => {
+                fresnel_schlick(albedo.value(rec.u, rec.v, &rec.p), cos_i)
+            }
+            ConductorFresnel::Complex(ior) => ior.fresnel(cos_i),
+        }
+    }
+}
+
+/// 粗糙导体，微表面上的镜面反射
+#[derive(Clone)]
+pub struct RoughConductor {
+    pub fresnel: ConductorFresnel,
+    pub microfacet: Microfacet,
+}
+
+impl RoughConductor {
+    pub fn new(r: Float, g: Float, b: Float, microfacet: Microfacet) -> RoughConductor {
+        RoughConductor::textured(Arc::new(SolidColor::new(r, g, b)), microfacet)
+    }
+
+    pub fn textured(albedo: AnyTexture, microfacet: Microfacet) -> RoughConductor {
+        RoughConductor {
+            fresnel: ConductorFresnel::Schlick(albedo),
+            microfacet,
+        }
+    }
+
+    /// 由复折射率描述的金属，例如 `ConductorPreset::Gold.ior()`
+    pub fn complex(ior: ComplexIor, microfacet: Microfacet) -> RoughConductor {
+        RoughConductor {
+            fresnel: ConductorFresnel::Complex(ior),
+            microfacet,
+        }
+    }
+}
+
+impl Material for RoughConductor {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        let wo = rec.to_local(&-ray.direction.unit());
+        let m = self.microfacet.sample(sampler);
+        let cos_om = wo.dot(&m);
+        if wo.z <= 0. || cos_om <= 0. {
+            return None;
+        }
+        let wi = microfacet::reflect(&wo, &m);
+        if wi.z <= 0. {
+            return None;
+        }
+        let f = self.fresnel.value(rec, cos_om);
+        Some(Scatter {
+            // F·D·G / (4 cosθo) 除以 D·cosθm / (4 wo·m)
+            attenuation: f * (self.microfacet.g(&wo, &wi) * cos_om / (wo.z * m.z)),
+            ray: rec.spawn_ray(rec.from_local(&wi), ray.t),
+            pdf: Some(self.microfacet.pdf(&m) / (4. * cos_om)),
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        let wo = rec.to_local(&-ray.direction.unit());
+        let wi = rec.to_local(&direction.unit());
+        if wo.z <= 0. || wi.z <= 0. {
+            return Vec3::zeros();
+        }
+        let m = (wo + wi).unit();
+        let f = self.fresnel.value(rec, wo.dot(&m));
+        f * (self.microfacet.d(&m) * self.microfacet.g(&wo, &wi) / (4. * wo.z))
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: &Vec3) -> Float {
+        let wo = rec.to_local(&-ray.direction.unit());
+        let wi = rec.to_local(&direction.unit());
+        if wo.z <= 0. || wi.z <= 0. {
+            return 0.;
+        }
+        let m = (wo + wi).unit();
+        self.microfacet.pdf(&m) / (4. * wo.dot(&m))
+    }
+
+    /// 垂直入射时的反射率
+    fn albedo(&self, rec: &HitRecord) -> Vec3 {
+        self.fresnel.value(rec, 1.)
+    }
+}
+
+/// 粗糙电介质，微表面上的反射与折射（Walter 等人 2007 年），例如磨砂玻璃
+#[derive(Debug, Clone)]
+pub struct RoughDielectric {
+    pub ior: Float,
+    pub microfacet: Microfacet,
+}
+
+impl RoughDielectric {
+    pub fn new(ior: Float, microfacet: Microfacet) -> RoughDielectric {
+        RoughDielectric { ior, microfacet }
+    }
+
+    /// 透射侧与入射侧的折射率之比
+    fn eta(&self, rec: &HitRecord) -> Float {
+        if rec.front_face {
+            self.ior
+        } else {
+            1. / self.ior
+        }
+    }
+
+    /// 给定入射方向时 `eval` 与 `pdf` 的值
+    fn evaluate(&self, ray: &Ray, rec: &HitRecord, direction: &Vec3) -> (Float, Float) {
+        let eta = self.eta(rec);
+        let wo = rec.to_local(&-ray.direction.unit());
+        let wi = rec.to_local(&direction.unit());
+        if wo.z <= 0. || wi.z == 0. {
+            return (0., 0.);
+        }
+        let reflect = wi.z > 0.;
+        // 折射时的广义半程向量
+        let m = if reflect { wo + wi } else { wi * eta + wo };
+        let m = if m.z < 0. { -m.unit() } else { m.unit() };
+        let (cos_om, cos_im) = (wo.dot(&m), wi.dot(&m));
+        // 微表面须同时朝向两个方向所在的一侧
+        if cos_om <= 0. || cos_im * wi.z <= 0. {
+            return (0., 0.);
+        }
+        let f = fresnel_dielectric(cos_om, eta);
+        let d = self.microfacet.d(&m);
+        let g = self.microfacet.g(&wo, &wi);
+        if reflect {
+            (
+                f * d * g / (4. * wo.z),
+                f * self.microfacet.pdf(&m) / (4. * cos_om),
+            )
+        } else {
+            let denom = (cos_im + cos_om / eta).powi(2);
+            (
+                (1. - f) * d * g * (cos_im * cos_om).abs() / (denom * wo.z),
+                (1. - f) * self.microfacet.pdf(&m) * cos_im.abs() / denom,
+            )
+        }
+    }
+}
+
+impl Material for RoughDielectric {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        let eta = self.eta(rec);
+        let wo = rec.to_local(&-ray.direction.unit());
+        let m = self.microfacet.sample(sampler);
+        let cos_om = wo.dot(&m);
+        if wo.z <= 0. || cos_om <= 0. {
+            return None;
+        }
+        // 以菲涅尔反射率选择反射或折射，全反射时必然反射
+        let wi = if sampler.random() < fresnel_dielectric(cos_om, eta) {
+            Some(microfacet::reflect(&wo, &m)).filter(|wi| wi.z > 0.)
+        } else {
+            microfacet::refract(&wo, &m, eta).filter(|wi| wi.z < 0.)
+        }?;
+        let direction = rec.from_local(&wi);
+        let (_, pdf) = self.evaluate(ray, rec, &direction);
+        Some(Scatter {
+            // 两种情形下 f·|cosθi| / pdf 都化简为 G·(wo·m) / (cosθo·cosθm)
+            attenuation: Vec3::ones() * (self.microfacet.g(&wo, &wi) * cos_om / (wo.z * m.z)),
+            ray: rec.spawn_ray(direction, ray.t),
+            pdf: Some(pdf),
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        Vec3::ones() * self.evaluate(ray, rec, direction).0
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: &Vec3) -> Float {
+        self.evaluate(ray, rec, direction).1
+    }
+
+    fn albedo(&self, _rec: &HitRecord) -> Vec3 {
+        Vec3::ones()
+    }
+}
+
+/// 迪士尼原理化 BSDF（Burley 2012, 2015）：以一组直观参数混合漫反射、光泽、镜面反射、清漆与透射
+///
+/// 漫反射与光泽只作用于非金属、不透明的部分，并位于镜面反射与清漆层之下；各向异性的拉伸方向为表面参数 u 增大的切线方向，见 `HitRecord::shading_basis`。
+#[derive(Clone)]
+pub struct Principled {
+    pub base_color: AnyTexture,
+    /// 0 为电介质，1 为金属，镜面反射颜色取自基础颜色
+    pub metallic: Float,
+    /// 感知粗糙度，平方后为微表面的 alpha
+    pub roughness: Float,
+    /// 电介质的镜面反射强度，0.5 对应法向反射率 4%
+    pub specular: Float,
+    /// 电介质的镜面反射向基础颜色着色的程度
+    pub specular_tint: Float,
+    /// 掠射角处的绒面光泽，用于布料
+    pub sheen: Float,
+    pub sheen_tint: Float,
+    /// 覆盖在表面上的第二层无色清漆
+    pub clearcoat: Float,
+    /// 清漆的光滑程度
+    pub clearcoat_gloss: Float,
+    /// 非金属部分中透射的比例，1 为玻璃
+    pub transmission: Float,
+    /// 透射部分的折射率
+    pub ior: Float,
+    /// 各向异性的程度，0 为各向同性
+    pub anisotropic: Float,
+}
+
+impl Principled {
+    pub fn new(r: Float, g: Float, b: Float) -> Principled {
+        Principled::textured(Arc::new(SolidColor::new(r, g, b)))
+    }
+
+    pub fn textured(base_color: AnyTexture) -> Principled {
+        Principled {
+            base_color,
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.,
+            sheen: 0.,
+            sheen_tint: 0.5,
+            clearcoat: 0.,
+            clearcoat_gloss: 1.,
+            transmission: 0.,
+            ior: 1.5,
+            anisotropic: 0.,
+        }
+    }
+
+    pub fn with_metallic(mut self, metallic: Float) -> Principled {
+        self.metallic = metallic.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_roughness(mut self, roughness: Float) -> Principled {
+        self.roughness = roughness.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_specular(mut self, specular: Float, tint: Float) -> Principled {
+        self.specular = specular.max(0.);
+        self.specular_tint = tint.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_sheen(mut self, sheen: Float, tint: Float) -> Principled {
+        self.sheen = sheen.max(0.);
+        self.sheen_tint = tint.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_clearcoat(mut self, clearcoat: Float, gloss: Float) -> Principled {
+        self.clearcoat = clearcoat.max(0.);
+        self.clearcoat_gloss = gloss.clamp(0., 1.);
+        self
+    }
+
+    pub fn with_transmission(mut self, transmission: Float, ior: Float) -> Principled {
+        self.transmission = transmission.clamp(0., 1.);
+        self.ior = ior;
+        self
+    }
+
+    pub fn with_anisotropic(mut self, anisotropic: Float) -> Principled {
+        self.anisotropic = anisotropic.clamp(0., 1.);
+        self
+    }
+
+    fn microfacet(&self) -> Microfacet {
+        let aspect = (1. - 0.9 * self.anisotropic).sqrt();
+        let alpha = self.roughness * self.roughness;
+        Microfacet::anisotropic(Distribution::Ggx, alpha / aspect, alpha * aspect)
+    }
+
+    /// 清漆的法线分布为 GTR1，遮蔽函数固定取 alpha 为 0.25 的 GGX
+    fn clearcoat_alpha(&self) -> Float {
+        0.1 + (0.001 - 0.1) * self.clearcoat_gloss
+    }
+
+    /// 漫反射、镜面反射、透射与清漆四个采样策略的选择概率，只取决于出射方向
+    fn lobe_weights(&self, wo: &Vec3, eta: Float) -> [Float; 4] {
+        let dielectric = 1. - self.metallic;
+        // 宏观上全反射时，倾斜的微表面仍可能透射，透射策略须保留一定的概率
+        let transmit =
+            dielectric * self.transmission * (1. - fresnel_dielectric(wo.z, eta)).max(0.25);
+        let weights = [
+            dielectric * (1. - self.transmission),
+            1. - transmit,
+            transmit,
+            0.25 * self.clearcoat,
+        ];
+        let sum: Float = weights.iter().sum();
+        weights.map(|w| w / sum)
+    }
+
+    /// 局部坐标系中的 BSDF 与余弦项之积以及 `scatter` 的概率密度
+    fn evaluate(&self, rec: &HitRecord, wo: &Vec3, wi: &Vec3) -> (Vec3, Float) {
+        if wo.z <= 0. || wi.z == 0. {
+            return (Vec3::zeros(), 0.);
+        }
+        let eta = if rec.front_face {
+            self.ior
+        } else {
+            1. / self.ior
+        };
+        let base_color = self.base_color.value(rec.u, rec.v, &rec.p);
+        let weights = self.lobe_weights(wo, eta);
+        let microfacet = self.microfacet();
+        let dielectric = 1. - self.metallic;
+        let opaque = dielectric * (1. - self.transmission);
+        let schlick_weight = |cos: Float| (1. - cos.clamp(0., 1.)).powi(5);
+        // 清漆覆盖在其他各层之上，被它反射的能量不再到达下层
+        let coat = (0.25 * self.clearcoat).min(1.);
+        let coat_fresnel = |cos: Float| coat * (0.04 + 0.96 * schlick_weight(cos));
+
+        if wi.z < 0. {
+            // 透射，半程向量与 `RoughDielectric` 相同
+            let m = *wi * eta + *wo;
+            let m = if m.z < 0. { -m.unit() } else { m.unit() };
+            let (cos_om, cos_im) = (wo.dot(&m), wi.dot(&m));
+            if cos_om <= 0. || cos_im >= 0. {
+                return (Vec3::zeros(), 0.);
+            }
+            let denom = (cos_im + cos_om / eta).powi(2);
+            let t = (1. - fresnel_dielectric(cos_om, eta))
+                * microfacet.d(&m)
+                * microfacet.g(wo, wi)
+                * (cos_im * cos_om).abs()
+                / (denom * wo.z);
+            // 进出物体各着色一次，穿过后恰为基础颜色
+            let tint = base_color.apply(|c| c.max(0.).sqrt());
+            let pdf = weights[2] * microfacet.pdf(&m) * cos_im.abs() / denom;
+            let under_coat = 1. - coat_fresnel(wo.z);
+            return (
+                tint * (dielectric * self.transmission * t * under_coat),
+                pdf,
+            );
+        }
+
+        let h = (*wo + *wi).unit();
+        let cos_d = wi.dot(&h);
+        if cos_d <= 0. {
+            return (Vec3::zeros(), 0.);
+        }
+        let tint = {
+            let luminance = luminance(&base_color);
+            if luminance > 0. {
+                base_color / luminance
+            } else {
+                Vec3::ones()
+            }
+        };
+        let mix = |a: Vec3, b: Vec3, t: Float| a * (1. - t) + b * t;
+
+        // 带有逆反射的漫反射
+        let fd90 = 0.5 + 2. * self.roughness * cos_d * cos_d;
+        let fd =
+            (1. + (fd90 - 1.) * schlick_weight(wi.z)) * (1. + (fd90 - 1.) * schlick_weight(wo.z));
+        let diffuse = base_color * (fd * FRAC_1_PI);
+        let sheen = mix(Vec3::ones(), tint, self.sheen_tint) * (self.sheen * schlick_weight(cos_d));
+
+        // 金属、不透明电介质与透射部分的反射共用同一个微表面分布
+        let f0 = mix(Vec3::ones(), tint, self.specular_tint) * (0.08 * self.specular);
+        let fresnel = fresnel_schlick(f0, cos_d) * opaque
+            + fresnel_schlick(base_color, cos_d) * self.metallic
+            + Vec3::ones() * (dielectric * self.transmission * fresnel_dielectric(cos_d, eta));
+        let specular = fresnel * (microfacet.d(&h) * microfacet.g(wo, wi) / (4. * wo.z * wi.z));
+
+        let clearcoat_alpha = self.clearcoat_alpha();
+        let clearcoat = coat_fresnel(cos_d)
+            * gtr1(h.z, clearcoat_alpha)
+            * Microfacet::new(Distribution::Ggx, 0.25).g(wo, wi)
+            / (4. * wo.z * wi.z);
+
+        // 漫反射与光泽位于电介质镜面层之下，只接收入射与出射方向上都未被镜面反射的能量；
+        // 再除以 1 减去半球平均反射率，补回层间多次反射的能量，施里克近似的平均值为 f0 + (1 - f0) / 21
+        let under_specular = (Vec3::ones() - fresnel_schlick(f0, wo.z))
+            * (Vec3::ones() - fresnel_schlick(f0, wi.z))
+            / (Vec3::ones() - (f0 + (Vec3::ones() - f0) / 21.));
+        let under_coat = (1. - coat_fresnel(wo.z)) * (1. - coat_fresnel(wi.z))
+            / (1. - coat * (0.04 + 0.96 / 21.));
+        let f = ((diffuse + sheen) * under_specular * opaque + specular) * under_coat
+            + Vec3::ones() * clearcoat;
+        let pdf = weights[0] * wi.z * FRAC_1_PI
+            + (weights[1] * microfacet.pdf(&h) + weights[3] * gtr1(h.z, clearcoat_alpha) * h.z)
+                / (4. * cos_d);
+        (f * wi.z, pdf)
+    }
+}
+
+/// Burley 的 GTR1 分布，拖尾比 GGX 更长
+fn gtr1(cos_h: Float, alpha: Float) -> Float {
+    let alpha2 = alpha * alpha;
+    (alpha2 - 1.) * FRAC_1_PI / (alpha2.ln() * (1. + (alpha2 - 1.) * cos_h * cos_h))
+}
+
+/// 按 GTR1(m)·cosθm 采样微表面法线
+fn sample_gtr1(alpha: Float, sampler: &mut dyn Sampler) -> Vec3 {
+    let u1 = sampler.random();
+    let u2 = sampler.random();
+    let alpha2 = alpha * alpha;
+    let cos_theta = ((1. - alpha2.powf(1. - u1)) / (1. - alpha2)).max(0.).sqrt();
+    let sin_theta = (1. - cos_theta * cos_theta).max(0.).sqrt();
+    let phi = 2. * PI * u2;
+    Vec3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+impl Material for Principled {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
+        let wo = rec.to_local(&-ray.direction.unit());
+        if wo.z <= 0. {
+            return None;
+        }
+        let eta = if rec.front_face {
+            self.ior
+        } else {
+            1. / self.ior
+        };
+        let weights = self.lobe_weights(&wo, eta);
+        let mut u = sampler.random();
+        let mut lobe = weights.len() - 1;
+        for (i, w) in weights.iter().enumerate() {
+            if u < *w {
+                lobe = i;
+                break;
+            }
+            u -= w;
+        }
+        let wi = match lobe {
+            0 => (Vec3::new(0., 0., 1.) + Vec3::random_unit_vector(sampler)).unit(),
+            1 => microfacet::reflect(&wo, &self.microfacet().sample(sampler)),
+            2 => microfacet::refract(&wo, &self.microfacet().sample(sampler), eta)?,
+            _ => microfacet::reflect(&wo, &sample_gtr1(self.clearcoat_alpha(), sampler)),
+        };
+        // 反射到表面以下或折射到表面以上的方向无效，概率密度中也不计入
+        if (wi.z < 0.) != (lobe == 2) {
+            return None;
+        }
+        let (f, pdf) = self.evaluate(rec, &wo, &wi);
+        if pdf <= 0. {
+            return None;
+        }
+        Some(Scatter {
+            attenuation: f / pdf,
+            ray: rec.spawn_ray(rec.from_local(&wi), ray.t),
+            pdf: Some(pdf),
+        })
+    }
+
+    fn eval(&self, ray: &Ray, rec: &HitRecord, direction: &Vec3) -> Vec3 {
+        let wo = rec.to_local(&-ray.direction.unit());
+        let wi = rec.to_local(&direction.unit());
+        self.evaluate(rec, &wo, &wi).0
+    }
+
+    fn pdf(&self, ray: &Ray, rec: &HitRecord, direction: &Vec3) -> Float {
+        let wo = rec.to_local(&-ray.direction.unit());
+        let wi = rec.to_local(&direction.unit());
+        self.evaluate(rec, &wo, &wi).1
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Vec3 {
+        self.base_color.value(rec.u, rec.v, &rec.p)
+    }
+}
+
+/// 漫射光源，向各方向均匀发光且不散射光线
+#[derive(Debug, Clone)]
+pub struct DiffuseLight {
+    pub emit: Vec3,
+}
+
+impl DiffuseLight {
+    pub fn new(r: Float, g: Float, b: Float) -> DiffuseLight {
+        DiffuseLight {
+            emit: Vec3::new(r, g, b),
+        }
+    }
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _ray: &Ray, _rec: &HitRecord, _sampler: &mut dyn Sampler) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _rec: &HitRecord) -> Vec3 {
+        self.emit
+    }
+
+    /// 按最大分量归一化的发光颜色
+    fn albedo(&self, _rec: &HitRecord) -> Vec3 {
+        let max = self.emit.x.max(self.emit.y).max(self.emit.z);
+        if max > 0. {
+            self.emit / max
+        } else {
+            Vec3::zeros()
+        }
+    }
+}
+
+/// 各向同性相函数，参与介质中的散射方向与入射方向无关
+#[derive(Clone)]
+pub struct Isotropic {
+    pub albedo: AnyTexture,
+}
+
+impl Isotropic {
+    pub fn new(r: Float, g: Float, b: Float) -> Isotropic {
+        Isotropic::textured(Arc::new(SolidColor::new(r, g, b)))
+    }
+
+    pub fn textured(albedo: AnyTexture) -> Isotropic {
+        Isotropic { albedo }
+    }
+}
+
+impl Material for Isotropic {
+    fn scatter(&self, ray: &Ray, rec: &HitRecord, sampler: &mut dyn Sampler) -> Option<Scatter> {
         Some(Scatter {
-            attenuation: Vec3::ones(),
-            ray: Ray::new(rec.p, direction),
+            attenuation: self.albedo.value(rec.u, rec.v, &rec.p),
+            // 介质内部的散射点不在表面上，无需偏移
+            ray: Ray::new_at(rec.p, Vec3::random_unit_vector(sampler), ray.t),
+            pdf: Some(UNIFORM_SPHERE_PDF),
         })
     }
+
+    fn eval(&self, _ray: &Ray, rec: &HitRecord, _direction: &Vec3) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, &rec.p) * UNIFORM_SPHERE_PDF
+    }
+
+    fn pdf(&self, _ray: &Ray, _rec: &HitRecord, _direction: &Vec3) -> Float {
+        UNIFORM_SPHERE_PDF
+    }
+
+    fn albedo(&self, rec: &HitRecord) -> Vec3 {
+        self.albedo.value(rec.u, rec.v, &rec.p)
+    }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub struct DummyMaterial {}
 
 impl Material for DummyMaterial {
-    fn scatter(&self, _ray: &Ray, _rec: &HitRecord) -> Option<Scatter> {
+    fn scatter(&self, _ray: &Ray, _rec: &HitRecord, _sampler: &mut dyn Sampler) -> Option<Scatter> {
         None
     }
 }
+
+#[test]
+fn test_dielectric_absorption() {
+    use crate::sampler::RandomSampler;
+
+    let absorption = Vec3::new(0.1, 0.5, 2.);
+    let material: AnyMaterial = Arc::new(Dielectric::absorbing(1.5, absorption));
+    let mut sampler = RandomSampler::new(0);
+    // 自内部射出，方向向量长度为 2，参数 t = 0.5 时走过的距离为 1
+    let mut ray = Ray::new(Vec3::zeros(), Vec3::new(0., 0., 2.));
+    ray.medium_distance = Some(0.);
+    let rec = HitRecord::create(&ray, 0.5, material.clone(), |_| Vec3::new(0., 0., 1.));
+    assert!(!rec.front_face);
+    let expected = (-absorption).apply(Float::exp);
+    for _ in 0..8 {
+        let scatter = material.scatter(&ray, &rec, &mut sampler).unwrap();
+        assert!((scatter.attenuation - expected).length() < 1e-5);
+    }
+    // 自外部进入时不衰减
+    let ray = Ray::new(Vec3::new(0., 0., 2.), Vec3::new(0., 0., -2.));
+    let rec = HitRecord::create(&ray, 0.5, material.clone(), |_| Vec3::new(0., 0., 1.));
+    assert!(rec.front_face);
+    let scatter = material.scatter(&ray, &rec, &mut sampler).unwrap();
+    assert_eq!(scatter.attenuation, Vec3::ones());
+    assert_eq!(scatter.ray.medium_distance, Some(0.));
+}
+
+#[test]
+fn test_dielectric_absorption_nested() {
+    use crate::hittable::{Hittable, HittableList};
+    use crate::sampler::RandomSampler;
+    use crate::sphere::Sphere;
+
+    // 半径 2 的吸收介质中嵌套半径 0.5 的镜面球，折射率为 1 时光线沿轴线直进直出，
+    // 在介质中先走 1.5 到达镜面，反射后再走 1.5 离开
+    let absorption = Vec3::new(0.1, 0.5, 2.);
+    let world = HittableList::from(vec![
+        Arc::new(Sphere::new(
+            Vec3::zeros(),
+            2.,
+            Arc::new(Dielectric::absorbing(1., absorption)),
+        )) as Arc<dyn Hittable>,
+        Arc::new(Sphere::new(
+            Vec3::zeros(),
+            0.5,
+            Arc::new(Metal::new(1., 1., 1., 0.)),
+        )),
+    ]);
+    let mut sampler = RandomSampler::new(0);
+    let mut ray = Ray::new(Vec3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+    let mut throughput = Vec3::ones();
+    let mut bounces = 0;
+    while let Some(rec) = world.hit(&ray, 0.001, Float::MAX) {
+        let scatter = rec.material.scatter(&ray, &rec, &mut sampler).unwrap();
+        throughput = throughput * scatter.attenuation;
+        ray = scatter.ray;
+        bounces += 1;
+    }
+    assert_eq!(bounces, 3);
+    assert!(ray.direction.z > 0. && ray.medium_distance.is_none());
+    let expected = (absorption * -3.).apply(Float::exp);
+    assert!((throughput - expected).length() < 1e-4, "{:?}", throughput);
+}
+
+#[test]
+fn test_white_furnace() {
+    use crate::sampler::RandomSampler;
+
+    // 反照率为 1 的材质在均匀光照下反射与透射的能量之和不超过入射能量；
+    // 只计单次散射，粗糙时微表面间多次反射的能量会损失，粗糙度低时几乎没有损失
+    let furnace = |material: AnyMaterial, cos_theta: Float, front_face: bool| -> Float {
+        let wo = Vec3::new((1. - cos_theta * cos_theta).sqrt(), 0., cos_theta);
+        let ray = Ray::new(wo, -wo);
+        let sign = if front_face { 1. } else { -1. };
+        let rec = HitRecord::create(&ray, 1., material.clone(), |_| Vec3::new(0., 0., sign));
+        let mut sampler = RandomSampler::new(3);
+        let n = 10000;
+        let mut sum = 0.;
+        for _ in 0..n {
+            if let Some(scatter) = material.scatter(&ray, &rec, &mut sampler) {
+                sum += scatter.attenuation.x;
+                // 采样结果与 eval / pdf 一致
+                let pdf = material.pdf(&ray, &rec, &scatter.ray.direction);
+                let expected = material.eval(&ray, &rec, &scatter.ray.direction).x / pdf;
+                assert!((scatter.pdf.unwrap() - pdf).abs() <= pdf * 1e-2);
+                assert!((scatter.attenuation.x - expected).abs() <= expected * 1e-2 + 1e-4);
+            }
+        }
+        sum / n as Float
+    };
+    for distribution in [Distribution::Ggx, Distribution::Beckmann] {
+        for alpha in [0.05, 0.3, 0.7, 1.] {
+            let microfacet = Microfacet::new(distribution, alpha);
+            for cos_theta in [1., 0.6, 0.2] {
+                let conductor = furnace(
+                    Arc::new(RoughConductor::new(1., 1., 1., microfacet)),
+                    cos_theta,
+                    true,
+                );
+                let glass = furnace(
+                    Arc::new(RoughDielectric::new(1.5, microfacet)),
+                    cos_theta,
+                    true,
+                );
+                let inside = furnace(
+                    Arc::new(RoughDielectric::new(1.5, microfacet)),
+                    cos_theta,
+                    false,
+                );
+                for energy in [conductor, glass, inside] {
+                    assert!(energy <= 1.01, "{:?} {} {}", distribution, alpha, energy);
+                    if alpha < 0.1 && cos_theta > 0.5 {
+                        assert!(energy > 0.99, "{:?} {} {}", distribution, alpha, energy);
+                    }
+                }
+            }
+        }
+    }
+
+    let principled = [
+        Principled::new(1., 1., 1.)
+            .with_metallic(1.)
+            .with_roughness(0.2),
+        Principled::new(1., 1., 1.)
+            .with_metallic(1.)
+            .with_roughness(0.8),
+        Principled::new(1., 1., 1.)
+            .with_transmission(1., 1.5)
+            .with_roughness(0.2),
+        Principled::new(1., 1., 1.).with_roughness(0.5),
+        Principled::new(1., 1., 1.)
+            .with_metallic(0.3)
+            .with_roughness(0.4)
+            .with_sheen(0.5, 0.5)
+            .with_clearcoat(1., 0.8)
+            .with_transmission(0.3, 1.45)
+            .with_anisotropic(0.7),
+        Principled::new(1., 1., 1.)
+            .with_roughness(1.)
+            .with_sheen(1., 0.),
+    ];
+    for (i, material) in principled.into_iter().enumerate() {
+        for cos_theta in [1., 0.6, 0.2] {
+            for front_face in [true, false] {
+                let energy = furnace(Arc::new(material.clone()), cos_theta, front_face);
+                assert!(energy <= 1.01, "{} {} {}", i, cos_theta, energy);
+                if i != 1 && cos_theta > 0.5 {
+                    assert!(energy > 0.9, "{} {} {}", i, cos_theta, energy);
+                }
+            }
+        }
+    }
+}
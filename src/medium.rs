@@ -0,0 +1,103 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::AnyMaterial;
+use crate::ray::Ray;
+use crate::vec3::{Float, Vec3};
+
+/// 密度均匀的参与介质，如雾与烟
+///
+/// 介质占据边界物体所围成的区域：光线自正面穿过边界时进入介质，自背面穿过时离开。
+/// 因此边界可以是嵌套的物体，例如以负半径内球挖空的球壳。
+pub struct ConstantMedium {
+    pub boundary: Arc<dyn Hittable>,
+    /// 单位长度上发生散射的概率密度
+    pub density: Float,
+    /// 相函数，通常为 `Isotropic`
+    pub phase_function: AnyMaterial,
+}
+
+impl ConstantMedium {
+    pub fn new(
+        boundary: Arc<dyn Hittable>,
+        density: Float,
+        phase_function: AnyMaterial,
+    ) -> ConstantMedium {
+        ConstantMedium {
+            boundary,
+            density,
+            phase_function,
+        }
+    }
+}
+
+impl Hittable for ConstantMedium {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let ray_length = ray.direction.length();
+        // 以光线携带的随机数按指数分布采样自由程，以世界空间中的距离计
+        let mut remaining = -(1. - ray.medium_sample).ln() / self.density;
+        let mut t = -Float::MAX;
+        let mut inside = false;
+        // 沿光线依次穿过边界，只在介质内部的区间上消耗自由程
+        while t < t_max {
+            let crossing = self.boundary.hit(ray, t, Float::MAX);
+            let end = crossing.as_ref().map_or(Float::MAX, |rec| rec.t);
+            let (a, b) = (t.max(t_min), end.min(t_max));
+            if inside && a < b {
+                let distance = (b - a) * ray_length;
+                if remaining < distance {
+                    let t = a + remaining / ray_length;
+                    let mut rec =
+                        HitRecord::create(ray, t, self.phase_function.clone(), |_| -ray.direction);
+                    // 法线与正反面对各向同性散射没有意义，仅保持记录完整
+                    rec.normal = Vec3::new(1., 0., 0.);
+                    return Some(rec);
+                }
+                remaining -= distance;
+            }
+            let crossing = crossing?;
+            inside = crossing.front_face;
+            // 避免再次击中同一交点
+            t = crossing.t + (crossing.t.abs() * 4. * Float::EPSILON).max(1e-4);
+        }
+        None
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.boundary.bounding_box()
+    }
+}
+
+#[test]
+fn test_constant_medium_hollow_boundary() {
+    use crate::hittable::HittableList;
+    use crate::material::{DummyMaterial, Isotropic};
+    use crate::sphere::Sphere;
+
+    let dummy: AnyMaterial = Arc::new(DummyMaterial {});
+    // 内半径 1、外半径 2 的球壳
+    let shell = HittableList::from(vec![
+        Arc::new(Sphere::new(Vec3::zeros(), 2., dummy.clone())) as Arc<dyn Hittable>,
+        Arc::new(Sphere::new(Vec3::zeros(), -1., dummy)),
+    ]);
+    let medium = ConstantMedium::new(Arc::new(shell), 1e6, Arc::new(Isotropic::new(1., 1., 1.)));
+
+    // 极高的密度下光线刚进入介质即散射
+    let ray = Ray::new(Vec3::new(0., 0., 5.), Vec3::new(0., 0., -2.));
+    let rec = medium.hit(&ray, 0.001, Float::MAX).unwrap();
+    assert!((rec.p.z - 2.).abs() < 1e-3);
+    // 从空腔中心出发的光线要先穿过空腔
+    let ray = Ray::new(Vec3::zeros(), Vec3::new(1., 0., 0.));
+    let rec = medium.hit(&ray, 0.001, Float::MAX).unwrap();
+    assert!((rec.p.x - 1.).abs() < 1e-3);
+    // 从介质内部出发的光线立即散射
+    let ray = Ray::new(Vec3::new(0., 1.5, 0.), Vec3::new(0., 1., 0.));
+    let rec = medium.hit(&ray, 0.001, Float::MAX).unwrap();
+    assert!((rec.p.y - 1.5).abs() < 1e-2);
+
+    // 密度极低时光线穿过整个球壳
+    let thin = ConstantMedium::new(medium.boundary.clone(), 1e-9, medium.phase_function.clone());
+    let ray = Ray::new(Vec3::new(0., 0., 5.), Vec3::new(0., 0., -1.));
+    assert!(thin.hit(&ray, 0.001, Float::MAX).is_none());
+}
@@ -0,0 +1,213 @@
+//! 微表面模型：GGX 与 Beckmann 法线分布及高度相关的 Smith 遮蔽-阴影函数
+//!
+//! 方向均在以宏观法线为 z 轴的局部坐标系中表示。粗糙度采用通常的 alpha 参数化，
+//! 即微表面斜率分布的尺度，0 为理想镜面，1 已非常粗糙；两个切线方向的粗糙度可以不同。
+
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::math::floats::consts::{FRAC_1_PI, PI};
+use crate::math::Float;
+use crate::sampler::Sampler;
+use crate::vec3::Vec3;
+
+/// 过小的 alpha 使法线分布近似 delta 函数，数值上不稳定
+const MIN_ALPHA: Float = 1e-3;
+
+/// 微表面法线分布
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Distribution {
+    /// Trowbridge-Reitz 分布，高光拖尾较长
+    #[default]
+    Ggx,
+    /// 高斯斜率分布
+    Beckmann,
+}
+
+impl FromStr for Distribution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "ggx" | "trowbridge-reitz" => Ok(Distribution::Ggx),
+            "beckmann" => Ok(Distribution::Beckmann),
+            _ => Err(format!("Unknown microfacet distribution {}", s)),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Microfacet {
+    pub distribution: Distribution,
+    /// 沿局部坐标系 x 轴方向的粗糙度
+    pub alpha_x: Float,
+    /// 沿局部坐标系 y 轴方向的粗糙度
+    pub alpha_y: Float,
+}
+
+impl Microfacet {
+    pub fn new(distribution: Distribution, alpha: Float) -> Microfacet {
+        Microfacet::anisotropic(distribution, alpha, alpha)
+    }
+
+    /// 各向异性的分布，例如拉丝金属
+    pub fn anisotropic(distribution: Distribution, alpha_x: Float, alpha_y: Float) -> Microfacet {
+        Microfacet {
+            distribution,
+            alpha_x: alpha_x.max(MIN_ALPHA),
+            alpha_y: alpha_y.max(MIN_ALPHA),
+        }
+    }
+
+    /// 法线分布函数 D(m)
+    pub fn d(&self, m: &Vec3) -> Float {
+        if m.z <= 0. {
+            return 0.;
+        }
+        let cos2 = m.z * m.z;
+        // 按两个方向的粗糙度缩放后的斜率平方，各向同性时为 tan²θ / α²
+        let e = ((m.x / self.alpha_x).powi(2) + (m.y / self.alpha_y).powi(2)) / cos2;
+        let norm = FRAC_1_PI / (self.alpha_x * self.alpha_y * cos2 * cos2);
+        match self.distribution {
+            Distribution::Ggx => norm / (1. + e).powi(2),
+            Distribution::Beckmann => norm * (-e).exp(),
+        }
+    }
+
+    /// Smith 辅助函数 Λ(w)，方向在宏观表面的哪一侧都可以
+    pub fn lambda(&self, w: &Vec3) -> Float {
+        let cos2 = w.z * w.z;
+        if cos2 <= 0. {
+            return Float::INFINITY;
+        }
+        // 投影到 w 方位角上的粗糙度与 tanθ 之积的平方
+        let alpha2_tan2 = ((w.x * self.alpha_x).powi(2) + (w.y * self.alpha_y).powi(2)) / cos2;
+        match self.distribution {
+            Distribution::Ggx => ((1. + alpha2_tan2).sqrt() - 1.) * 0.5,
+            Distribution::Beckmann => {
+                // Walter 等人 2007 年的有理近似
+                let a = 1. / alpha2_tan2.sqrt();
+                if a >= 1.6 {
+                    0.
+                } else {
+                    (1. - 1.259 * a + 0.396 * a * a) / (3.535 * a + 2.181 * a * a)
+                }
+            }
+        }
+    }
+
+    /// 单向遮蔽函数 G1(w)
+    pub fn g1(&self, w: &Vec3) -> Float {
+        1. / (1. + self.lambda(w))
+    }
+
+    /// 高度相关的遮蔽-阴影函数 G(wo, wi)
+    pub fn g(&self, wo: &Vec3, wi: &Vec3) -> Float {
+        1. / (1. + self.lambda(wo) + self.lambda(wi))
+    }
+
+    /// 按 D(m)·cosθm 采样微表面法线，概率密度见 `pdf`
+    pub fn sample(&self, sampler: &mut dyn Sampler) -> Vec3 {
+        let u1 = sampler.random();
+        let u2 = sampler.random();
+        // 先采样粗糙度为 1 的各向同性斜率，再沿两个方向拉伸
+        let r2 = match self.distribution {
+            Distribution::Ggx => u1 / (1. - u1),
+            Distribution::Beckmann => -(1. - u1).ln(),
+        };
+        let phi = 2. * PI * u2;
+        let r = r2.sqrt();
+        Vec3::new(
+            self.alpha_x * r * phi.cos(),
+            self.alpha_y * r * phi.sin(),
+            1.,
+        )
+        .unit()
+    }
+
+    /// `sample` 得到法线 `m` 的概率密度
+    pub fn pdf(&self, m: &Vec3) -> Float {
+        self.d(m) * m.z.max(0.)
+    }
+}
+
+/// 电介质界面的菲涅尔反射率，`cos_i` 为入射角余弦，`eta` 为透射侧与入射侧折射率之比
+pub fn fresnel_dielectric(cos_i: Float, eta: Float) -> Float {
+    let cos_i = cos_i.clamp(0., 1.);
+    let sin2_t = (1. - cos_i * cos_i) / (eta * eta);
+    if sin2_t >= 1. {
+        // 全反射
+        return 1.;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    let r_parallel = (eta * cos_i - cos_t) / (eta * cos_i + cos_t);
+    let r_perpendicular = (cos_i - eta * cos_t) / (cos_i + eta * cos_t);
+    (r_parallel * r_parallel + r_perpendicular * r_perpendicular) * 0.5
+}
+
+/// 以法向反射率 `f0` 为参数的施里克近似
+pub fn fresnel_schlick(f0: Vec3, cos_i: Float) -> Vec3 {
+    f0 + (Vec3::ones() - f0) * (1. - cos_i.clamp(0., 1.)).powi(5)
+}
+
+/// 方向 `wo` 关于法线 `m` 的镜面反射
+pub fn reflect(wo: &Vec3, m: &Vec3) -> Vec3 {
+    *m * (2. * wo.dot(m)) - *wo
+}
+
+/// 方向 `wo` 经法线 `m` 折射后的方向，`eta` 为透射侧与入射侧折射率之比，全反射时返回 `None`
+pub fn refract(wo: &Vec3, m: &Vec3, eta: Float) -> Option<Vec3> {
+    let cos_i = wo.dot(m);
+    let sin2_t = (1. - cos_i * cos_i).max(0.) / (eta * eta);
+    if sin2_t >= 1. {
+        return None;
+    }
+    let cos_t = (1. - sin2_t).sqrt();
+    Some(-*wo / eta + *m * (cos_i / eta - cos_t))
+}
+
+#[test]
+fn test_distribution_normalized() {
+    use crate::sampler::RandomSampler;
+
+    // 投影面积归一化：∫ D(m) cosθm dm = 1，以及 Smith 遮蔽函数满足 ∫ G1(w) max(0, w·m) D(m) dm = cosθw
+    let mut sampler = RandomSampler::new(7);
+    let n = 200000;
+    for distribution in [Distribution::Ggx, Distribution::Beckmann] {
+        for (alpha_x, alpha_y) in [(0.3, 0.3), (0.6, 0.6), (1., 1.), (0.3, 0.8)] {
+            let microfacet = Microfacet::anisotropic(distribution, alpha_x, alpha_y);
+            let w = Vec3::new(0.48, 0.36, 0.8);
+            let mut area = 0.;
+            let mut projected = 0.;
+            for _ in 0..n {
+                // 在上半球面均匀采样
+                let v = Vec3::random_unit_vector(&mut sampler);
+                let m = Vec3::new(v.x, v.y, v.z.abs());
+                let weight = 2. * PI;
+                area += microfacet.d(&m) * m.z * weight;
+                projected += microfacet.g1(&w) * w.dot(&m).max(0.) * microfacet.d(&m) * weight;
+            }
+            let (area, projected) = (area / n as Float, projected / n as Float);
+            assert!(
+                (area - 1.).abs() < 0.05,
+                "{:?} {} {} {}",
+                distribution,
+                alpha_x,
+                alpha_y,
+                area
+            );
+            assert!(
+                (projected - w.z).abs() < 0.05,
+                "{:?} {} {} {}",
+                distribution,
+                alpha_x,
+                alpha_y,
+                projected
+            );
+        }
+    }
+    assert_eq!("GGX".parse(), Ok(Distribution::Ggx));
+    assert!("phong".parse::<Distribution>().is_err());
+}
@@ -0,0 +1,524 @@
+//! Wavefront OBJ 与 MTL 文件的读取
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::image::ImageError;
+use crate::material::{AnyMaterial, Dielectric, Lambertian, Metal, Principled};
+use crate::texture::{AnyTexture, ImageTexture, SolidColor};
+use crate::triangle::{MeshFace, TriangleMesh};
+use crate::vec3::{Float, Vec3};
+
+#[derive(Debug)]
+pub enum ObjError {
+    IOError(std::io::Error),
+    ImageError(ImageError),
+    /// 文件内容有误，`line` 从 1 开始计数
+    ParseError {
+        file: String,
+        line: usize,
+        message: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::IOError(e) => write!(f, "{}", e),
+            ObjError::ImageError(e) => write!(f, "{:?}", e),
+            ObjError::ParseError {
+                file,
+                line,
+                message,
+            } => write!(f, "{}:{}: {}", file, line, message),
+        }
+    }
+}
+
+/// MTL 文件中的一项材质定义
+#[derive(Debug, Clone, PartialEq)]
+pub struct MtlEntry {
+    /// 漫反射颜色
+    pub kd: Vec3,
+    /// 镜面反射颜色
+    pub ks: Vec3,
+    /// 镜面指数
+    pub ns: Float,
+    /// 折射率
+    pub ni: Float,
+    /// 不透明度
+    pub d: Float,
+    /// 光照模型
+    pub illum: u32,
+    /// 漫反射贴图的文件名
+    pub map_kd: Option<String>,
+    /// PBR 扩展：粗糙度
+    pub pr: Option<Float>,
+    /// PBR 扩展：金属度
+    pub pm: Option<Float>,
+    /// PBR 扩展：绒面光泽
+    pub ps: Option<Float>,
+    /// PBR 扩展：清漆强度
+    pub pc: Option<Float>,
+    /// PBR 扩展：清漆粗糙度
+    pub pcr: Option<Float>,
+    /// PBR 扩展：各向异性
+    pub aniso: Option<Float>,
+}
+
+impl Default for MtlEntry {
+    fn default() -> Self {
+        MtlEntry {
+            kd: Vec3::from(0.8),
+            ks: Vec3::zeros(),
+            ns: 0.,
+            ni: 1.,
+            d: 1.,
+            illum: 1,
+            map_kd: None,
+            pr: None,
+            pm: None,
+            ps: None,
+            pc: None,
+            pcr: None,
+            aniso: None,
+        }
+    }
+}
+
+impl MtlEntry {
+    /// 是否使用了 PBR 扩展的参数
+    pub fn is_pbr(&self) -> bool {
+        [self.pr, self.pm, self.ps, self.pc, self.pcr, self.aniso]
+            .iter()
+            .any(Option::is_some)
+    }
+
+    /// 使用 PBR 扩展的映射为原理化材质，其余映射到最接近的内置材质：
+    /// 透明的映射为玻璃，开启反射或镜面颜色非零的映射为金属，其余为博朗材质；
+    /// 原理化材质与博朗材质优先使用漫反射贴图 `diffuse_map`
+    pub fn to_material(&self, diffuse_map: Option<AnyTexture>) -> AnyMaterial {
+        let transparent = self.d < 1. || matches!(self.illum, 4 | 6 | 7 | 9);
+        // illum 2 是带高光的漫反射，这里只保留漫反射部分；3 与 5 才启用光线追踪的反射
+        let reflective = matches!(self.illum, 3 | 5);
+        let ni = if self.ni > 1. { self.ni } else { 1.5 };
+        if self.is_pbr() {
+            let base_color = diffuse_map
+                .unwrap_or_else(|| Arc::new(SolidColor::new(self.kd.x, self.kd.y, self.kd.z)));
+            let transmission = if self.d < 1. {
+                1. - self.d.max(0.)
+            } else if transparent {
+                1.
+            } else {
+                0.
+            };
+            return Arc::new(
+                Principled::textured(base_color)
+                    .with_metallic(self.pm.unwrap_or(0.))
+                    .with_roughness(self.pr.unwrap_or(0.5))
+                    .with_sheen(self.ps.unwrap_or(0.), 0.5)
+                    .with_clearcoat(self.pc.unwrap_or(0.), 1. - self.pcr.unwrap_or(0.))
+                    .with_transmission(transmission, ni)
+                    .with_anisotropic(self.aniso.unwrap_or(0.)),
+            );
+        }
+        if transparent {
+            Arc::new(Dielectric::new(ni))
+        } else if reflective {
+            // 由 Phong 镜面指数换算 Beckmann 粗糙度作为模糊程度
+            let fuzz = (2. / (self.ns + 2.)).sqrt();
+            Arc::new(Metal::new(self.ks.x, self.ks.y, self.ks.z, fuzz))
+        } else if let Some(texture) = diffuse_map {
+            Arc::new(Lambertian::textured(texture))
+        } else {
+            Arc::new(Lambertian::new(self.kd.x, self.kd.y, self.kd.z))
+        }
+    }
+}
+
+/// 逐行读取时的位置信息，用于生成带行号的错误
+struct Cursor<'a> {
+    file: &'a str,
+    line: usize,
+}
+
+impl Cursor<'_> {
+    fn error<T>(&self, message: String) -> Result<T, ObjError> {
+        Err(ObjError::ParseError {
+            file: self.file.to_string(),
+            line: self.line,
+            message,
+        })
+    }
+
+    fn floats<'b, I>(&self, args: I, n: usize) -> Result<Vec<Float>, ObjError>
+    where
+        I: Iterator<Item = &'b str>,
+    {
+        let values = args
+            .map(|arg| {
+                arg.parse::<Float>()
+                    .or_else(|_| self.error(format!("invalid number `{}`", arg)))
+            })
+            .collect::<Result<Vec<Float>, ObjError>>()?;
+        if values.len() < n {
+            self.error(format!("expected {} numbers, found {}", n, values.len()))
+        } else {
+            Ok(values)
+        }
+    }
+
+    fn vec3<'b, I>(&self, args: I) -> Result<Vec3, ObjError>
+    where
+        I: Iterator<Item = &'b str>,
+    {
+        let v = self.floats(args, 3)?;
+        Ok(Vec3::new(v[0], v[1], v[2]))
+    }
+
+    /// 将 OBJ 中从 1 开始、可为负数（相对末尾）的下标转换为从 0 开始的下标
+    fn index(&self, token: &str, len: usize, kind: &str) -> Result<usize, ObjError> {
+        let index = token
+            .parse::<isize>()
+            .or_else(|_| self.error(format!("invalid {} index `{}`", kind, token)))?;
+        let resolved = if index > 0 {
+            index - 1
+        } else {
+            len as isize + index
+        };
+        if index == 0 || resolved < 0 || resolved >= len as isize {
+            self.error(format!("{} index {} out of range", kind, index))
+        } else {
+            Ok(resolved as usize)
+        }
+    }
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, ObjError> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(ObjError::IOError)
+}
+
+/// 逐行拆分为关键字与参数，跳过空行与注释
+fn for_each_statement<R, F>(reader: R, file: &str, mut f: F) -> Result<(), ObjError>
+where
+    R: BufRead,
+    F: FnMut(&Cursor, &str, std::str::SplitWhitespace) -> Result<(), ObjError>,
+{
+    for (i, line) in reader.lines().enumerate() {
+        let line = line.map_err(ObjError::IOError)?;
+        let cursor = Cursor { file, line: i + 1 };
+        let content = line.split('#').next().unwrap_or("");
+        let mut tokens = content.split_whitespace();
+        if let Some(keyword) = tokens.next() {
+            f(&cursor, keyword, tokens)?;
+        }
+    }
+    Ok(())
+}
+
+/// 属于某项材质定义、不能出现在第一个 `newmtl` 之前的关键字
+const MTL_KEYWORDS: [&str; 12] = [
+    "Kd", "Ks", "Ns", "Ni", "d", "illum", "Pr", "Pm", "Ps", "Pc", "Pcr", "aniso",
+];
+
+/// 读取 MTL 文件，按出现顺序返回各项材质定义
+pub fn parse_mtl<R: BufRead>(reader: R, file: &str) -> Result<Vec<(String, MtlEntry)>, ObjError> {
+    let mut entries: Vec<(String, MtlEntry)> = vec![];
+    for_each_statement(reader, file, |cursor, keyword, mut args| {
+        if keyword == "newmtl" {
+            return match args.next() {
+                Some(name) => {
+                    entries.push((name.to_string(), MtlEntry::default()));
+                    Ok(())
+                }
+                None => cursor.error("missing material name".to_string()),
+            };
+        }
+        let entry = match entries.last_mut() {
+            Some((_, entry)) => entry,
+            None if MTL_KEYWORDS.contains(&keyword) => {
+                return cursor.error(format!("`{}` before any `newmtl`", keyword));
+            }
+            None => return Ok(()),
+        };
+        match keyword {
+            "Kd" => entry.kd = cursor.vec3(args)?,
+            "Ks" => entry.ks = cursor.vec3(args)?,
+            "Ns" => entry.ns = cursor.floats(args, 1)?[0],
+            "Ni" => entry.ni = cursor.floats(args, 1)?[0],
+            "d" => entry.d = cursor.floats(args, 1)?[0],
+            "Tr" => entry.d = 1. - cursor.floats(args, 1)?[0],
+            "Pr" => entry.pr = Some(cursor.floats(args, 1)?[0]),
+            "Pm" => entry.pm = Some(cursor.floats(args, 1)?[0]),
+            "Ps" => entry.ps = Some(cursor.floats(args, 1)?[0]),
+            "Pc" => entry.pc = Some(cursor.floats(args, 1)?[0]),
+            "Pcr" => entry.pcr = Some(cursor.floats(args, 1)?[0]),
+            "aniso" => entry.aniso = Some(cursor.floats(args, 1)?[0]),
+            // 贴图选项（如 -s、-o）暂不支持，文件名取最后一个参数
+            "map_Kd" => match args.last() {
+                Some(name) => entry.map_kd = Some(name.to_string()),
+                None => return cursor.error("missing texture file name".to_string()),
+            },
+            "illum" => {
+                let arg = args.next().unwrap_or("");
+                entry.illum = arg
+                    .parse()
+                    .or_else(|_| cursor.error(format!("invalid illumination model `{}`", arg)))?;
+            }
+            // 其余项（其他贴图、环境光颜色等）暂不支持
+            _ => {}
+        }
+        Ok(())
+    })?;
+    Ok(entries)
+}
+
+/// 读取 OBJ 内容；`base_dir` 为 `mtllib` 引用的 MTL 文件所在目录，为 `None` 时不读取 MTL
+pub fn parse_obj<R: BufRead>(
+    reader: R,
+    file: &str,
+    base_dir: Option<&Path>,
+) -> Result<TriangleMesh, ObjError> {
+    parse_obj_recording(reader, file, base_dir, &mut vec![])
+}
+
+/// 同 `parse_obj`，并将读取的 MTL 文件与贴图依次记入 `files`
+fn parse_obj_recording<R: BufRead>(
+    reader: R,
+    file: &str,
+    base_dir: Option<&Path>,
+    files: &mut Vec<PathBuf>,
+) -> Result<TriangleMesh, ObjError> {
+    let mut positions: Vec<Vec3> = vec![];
+    let mut normals: Vec<Vec3> = vec![];
+    let mut uvs: Vec<(Float, Float)> = vec![];
+    let mut faces: Vec<MeshFace> = vec![];
+    // 下标 0 为未指定材质时使用的默认材质
+    let mut materials: Vec<AnyMaterial> = vec![MtlEntry::default().to_material(None)];
+    let mut library: HashMap<String, usize> = HashMap::new();
+    let mut current_material = 0;
+    let mut group = String::from("default");
+
+    for_each_statement(reader, file, |cursor, keyword, mut args| {
+        match keyword {
+            "v" => positions.push(cursor.vec3(args)?),
+            "vn" => normals.push(cursor.vec3(args)?),
+            "vt" => {
+                let v = cursor.floats(args, 1)?;
+                uvs.push((v[0], v.get(1).copied().unwrap_or(0.)));
+            }
+            "f" => {
+                let mut corners = vec![];
+                for token in args {
+                    let mut parts = token.split('/');
+                    let v = parts.next().unwrap_or("");
+                    let vt = parts.next().filter(|s| !s.is_empty());
+                    let vn = parts.next().filter(|s| !s.is_empty());
+                    corners.push((
+                        cursor.index(v, positions.len(), "vertex")?,
+                        vt.map(|vt| cursor.index(vt, uvs.len(), "texture coordinate"))
+                            .transpose()?,
+                        vn.map(|vn| cursor.index(vn, normals.len(), "normal"))
+                            .transpose()?,
+                    ));
+                }
+                if corners.len() < 3 {
+                    return cursor.error(format!(
+                        "face in group `{}` has {} vertices, at least 3 required",
+                        group,
+                        corners.len()
+                    ));
+                }
+                let has_uvs = corners.iter().all(|c| c.1.is_some());
+                let has_normals = corners.iter().all(|c| c.2.is_some());
+                // 以扇形方式将多边形三角化
+                for i in 1..corners.len() - 1 {
+                    let tri = [corners[0], corners[i], corners[i + 1]];
+                    faces.push(MeshFace {
+                        positions: tri.map(|c| c.0),
+                        uvs: if has_uvs {
+                            Some(tri.map(|c| c.1.unwrap()))
+                        } else {
+                            None
+                        },
+                        normals: if has_normals {
+                            Some(tri.map(|c| c.2.unwrap()))
+                        } else {
+                            None
+                        },
+                        material: current_material,
+                    });
+                }
+            }
+            "g" | "o" => group = args.collect::<Vec<&str>>().join(" "),
+            "mtllib" => {
+                if let Some(base_dir) = base_dir {
+                    for name in args {
+                        let path = base_dir.join(name);
+                        let entries = parse_mtl(open(&path)?, path.to_string_lossy().as_ref())?;
+                        let mtl_dir = path.parent().unwrap_or(base_dir).to_path_buf();
+                        files.push(path);
+                        for (name, entry) in entries {
+                            let diffuse_map = match &entry.map_kd {
+                                Some(file) => {
+                                    let path = mtl_dir.join(file);
+                                    let texture =
+                                        ImageTexture::load(&path).map_err(ObjError::ImageError)?;
+                                    files.push(path);
+                                    Some(Arc::new(texture) as AnyTexture)
+                                }
+                                None => None,
+                            };
+                            library.insert(name, materials.len());
+                            materials.push(entry.to_material(diffuse_map));
+                        }
+                    }
+                }
+            }
+            "usemtl" => {
+                let name = args.next().unwrap_or("");
+                current_material = match library.get(name) {
+                    Some(&index) => index,
+                    None if base_dir.is_none() => 0,
+                    None => return cursor.error(format!("unknown material `{}`", name)),
+                };
+            }
+            // 平滑组、线段、点等暂不支持
+            _ => {}
+        }
+        Ok(())
+    })?;
+
+    Ok(TriangleMesh::new(positions, normals, uvs, materials, faces))
+}
+
+/// 读取 OBJ 文件及其引用的 MTL 文件
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<TriangleMesh, ObjError> {
+    load_obj_recording(path).map(|(mesh, _)| mesh)
+}
+
+/// 同 `load_obj`，并返回读取过的全部文件：OBJ 文件本身、MTL 文件与贴图
+pub fn load_obj_recording<P: AsRef<Path>>(
+    path: P,
+) -> Result<(TriangleMesh, Vec<PathBuf>), ObjError> {
+    let path = path.as_ref();
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let mut files = vec![path.to_path_buf()];
+    let mesh = parse_obj_recording(
+        open(path)?,
+        path.to_string_lossy().as_ref(),
+        Some(base_dir),
+        &mut files,
+    )?;
+    Ok((mesh, files))
+}
+
+#[test]
+fn test_parse_obj() {
+    use crate::hittable::Hittable;
+    use crate::ray::Ray;
+
+    let source = "\
+# 单位正方形，四边形面
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 1 1
+vt 0 1
+vn 0 0 1
+g square
+f 1/1/1 2/2/1 3/3/1 -1/-1/-1
+";
+    let mesh = parse_obj(source.as_bytes(), "square.obj", None).unwrap();
+    let ray = Ray::new(Vec3::new(0.25, 0.75, 1.), Vec3::new(0., 0., -1.));
+    let rec = mesh.hit(&ray, 0.001, Float::MAX).unwrap();
+    assert_eq!(rec.t, 1.);
+    assert!((rec.u - 0.25).abs() < 1e-6 && (rec.v - 0.75).abs() < 1e-6);
+    assert_eq!(rec.normal, Vec3::new(0., 0., 1.));
+}
+
+#[test]
+fn test_parse_errors() {
+    let source = "v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n";
+    match parse_obj(source.as_bytes(), "bad.obj", None) {
+        Err(ObjError::ParseError { line, .. }) => assert_eq!(line, 5),
+        _ => panic!("expected a parse error"),
+    }
+    let source = "newmtl red\nKd 1 0 zero\n";
+    match parse_mtl(source.as_bytes(), "bad.mtl") {
+        Err(ObjError::ParseError { line, .. }) => assert_eq!(line, 2),
+        _ => panic!("expected a parse error"),
+    }
+}
+
+#[test]
+fn test_parse_mtl() {
+    let source = "\
+newmtl glass
+Ni 1.5
+d 0.5
+illum 4
+
+newmtl gold
+Ks 1 0.8 0.3
+Ns 100
+illum 3
+
+newmtl brushed
+Kd 0.9 0.9 0.9
+Pm 1
+Pr 0.3
+aniso 0.8
+";
+    let entries = parse_mtl(source.as_bytes(), "test.mtl").unwrap();
+    assert_eq!(entries.len(), 3);
+    assert_eq!(entries[0].0, "glass");
+    assert_eq!(entries[0].1.ni, 1.5);
+    assert_eq!(entries[0].1.illum, 4);
+    assert_eq!(entries[1].1.ks, Vec3::new(1., 0.8, 0.3));
+    assert_eq!(entries[1].1.ns, 100.);
+    assert!(!entries[1].1.is_pbr());
+    assert_eq!(entries[2].1.pm, Some(1.));
+    assert_eq!(entries[2].1.aniso, Some(0.8));
+    assert!(entries[2].1.is_pbr());
+}
+
+#[test]
+fn test_mtl_illumination_models() {
+    use crate::hittable::HitRecord;
+    use crate::ray::Ray;
+
+    let source = "\
+newmtl plastic
+Kd 0.8 0.2 0.1
+Ks 0.5 0.5 0.5
+Ns 50
+illum 2
+
+newmtl mirror
+Kd 0.8 0.2 0.1
+Ks 0.9 0.9 0.9
+illum 3
+";
+    let entries = parse_mtl(source.as_bytes(), "test.mtl").unwrap();
+    let ray = Ray::new(Vec3::new(0., 0., 1.), Vec3::new(0., 0., -1.));
+    // illum 2 的高光不改变漫反射颜色
+    let plastic = entries[0].1.to_material(None);
+    let rec = HitRecord::create(&ray, 1., plastic.clone(), |_| Vec3::new(0., 0., 1.));
+    assert_eq!(plastic.albedo(&rec), Vec3::new(0.8, 0.2, 0.1));
+    let direction = Vec3::new(0., 0.6, 0.8);
+    assert!(plastic.eval(&ray, &rec, &direction).length() > 0.);
+    // illum 3 为反射
+    let mirror = entries[1].1.to_material(None);
+    let rec = HitRecord::create(&ray, 1., mirror.clone(), |_| Vec3::new(0., 0., 1.));
+    assert_eq!(mirror.albedo(&rec), Vec3::new(0.9, 0.9, 0.9));
+}
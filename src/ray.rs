@@ -1,11 +1,17 @@
 use crate::math::Float;
 use crate::vec3::Vec3;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy)]
 pub struct Ray {
     pub origin: Vec3,
     pub direction: Vec3,
+    /// 光线发出的时刻，用于运动模糊
     pub t: Float,
+    /// 到达起点之前已在吸收介质中走过的距离，不在吸收介质中时为 `None`
+    pub medium_distance: Option<Float>,
+    /// 参与介质采样自由程所用的 [0, 1) 均匀随机数，由积分器在每次弹射时抽取，
+    /// 求交因此不消耗采样器，结果与遍历顺序无关；未抽取时取 0.5，即自由程的中位数
+    pub medium_sample: Float,
 }
 
 impl Ray {
@@ -14,6 +20,8 @@ impl Ray {
             origin,
             direction,
             t: 0.0,
+            medium_distance: None,
+            medium_sample: 0.5,
         }
     }
 
@@ -22,10 +30,24 @@ impl Ray {
             origin,
             direction,
             t,
+            medium_distance: None,
+            medium_sample: 0.5,
         }
     }
 
+    /// 换用 `sample` 采样参与介质中的自由程
+    pub fn with_medium_sample(mut self, sample: Float) -> Self {
+        self.medium_sample = sample;
+        self
+    }
+
     pub fn at(&self, t: Float) -> Vec3 {
         self.origin + self.direction * t
     }
+
+    /// 到达参数 `t` 处时在吸收介质中走过的总距离
+    pub fn medium_distance_at(&self, t: Float) -> Option<Float> {
+        self.medium_distance
+            .map(|distance| distance + t * self.direction.length())
+    }
 }
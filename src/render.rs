@@ -0,0 +1,1175 @@
+//! 与命令行无关的渲染接口，供其它程序直接嵌入
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use rayon::prelude::*;
+
+use crate::aov::{aov_filename, Aov, AovSet};
+use crate::camera::Camera;
+use crate::checkpoint::{Checkpoint, CheckpointError, PixelState};
+use crate::color::srgb_to_linear;
+use crate::film::{Film, FilmTile, Filter};
+use crate::image::{self, ExrCompression, ExrPrecision, ExrSamples, ImageError, ImageFormat};
+use crate::math::Float;
+use crate::sampler::{hash, SamplerKind};
+use crate::scene::RayColorFn;
+use crate::tile::{self, Tile, TileOrder};
+use crate::tonemap::{luminance, ToneMapping};
+use crate::vec3::Vec3;
+use crate::world::World;
+
+/// 图像大小与采样参数
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RenderSettings {
+    pub width: usize,
+    pub height: usize,
+    /// 启用自适应采样时为平均每像素的样本预算
+    pub samples_per_pixel: usize,
+    pub max_depth: i32,
+    /// 每个样本的随机数由种子、像素坐标与样本序号确定，结果与线程数无关
+    pub seed: u64,
+    pub sampler: SamplerKind,
+    pub adaptive: Option<AdaptiveSampling>,
+    /// 样本按该滤波器累加到周围的像素，默认的盒形滤波器即每个像素内的样本平均
+    pub filter: Filter,
+    /// 分块边长，单位为像素
+    pub tile_size: usize,
+    pub tile_order: TileOrder,
+    /// 渐进式渲染时每一轮为每个像素采集的样本数，`None` 时一次采完
+    pub samples_per_pass: Option<usize>,
+    /// 与颜色一同累加的辅助通道
+    pub aovs: AovSet,
+}
+
+impl RenderSettings {
+    pub fn new(width: usize, height: usize) -> RenderSettings {
+        RenderSettings {
+            width,
+            height,
+            samples_per_pixel: 500,
+            max_depth: 50,
+            seed: 0,
+            sampler: SamplerKind::Random,
+            adaptive: None,
+            filter: Filter::default(),
+            tile_size: 16,
+            tile_order: TileOrder::Spiral,
+            samples_per_pass: None,
+            aovs: AovSet::new(),
+        }
+    }
+
+    pub fn with_samples_per_pixel(mut self, samples_per_pixel: usize) -> RenderSettings {
+        self.samples_per_pixel = samples_per_pixel;
+        self
+    }
+
+    pub fn with_max_depth(mut self, max_depth: i32) -> RenderSettings {
+        self.max_depth = max_depth;
+        self
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> RenderSettings {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_sampler(mut self, sampler: SamplerKind) -> RenderSettings {
+        self.sampler = sampler;
+        self
+    }
+
+    pub fn with_adaptive(mut self, adaptive: AdaptiveSampling) -> RenderSettings {
+        self.adaptive = Some(adaptive);
+        self
+    }
+
+    pub fn with_filter(mut self, filter: Filter) -> RenderSettings {
+        self.filter = filter;
+        self
+    }
+
+    pub fn with_tile_size(mut self, tile_size: usize) -> RenderSettings {
+        self.tile_size = tile_size;
+        self
+    }
+
+    pub fn with_tile_order(mut self, tile_order: TileOrder) -> RenderSettings {
+        self.tile_order = tile_order;
+        self
+    }
+
+    pub fn with_samples_per_pass(mut self, samples_per_pass: usize) -> RenderSettings {
+        self.samples_per_pass = Some(samples_per_pass);
+        self
+    }
+
+    pub fn with_aovs(mut self, aovs: AovSet) -> RenderSettings {
+        self.aovs = aovs;
+        self
+    }
+
+    /// 决定渲染结果的参数的哈希；分块与每轮样本数改变浮点数的累加顺序，同样计入其中
+    #[allow(clippy::unnecessary_cast)] // 启用双精度时 Float 为 f64
+    pub fn fingerprint(&self) -> u64 {
+        let adaptive = match self.adaptive {
+            Some(a) => [
+                1,
+                a.threshold.to_bits() as u64,
+                a.min_samples as u64,
+                a.batch_size as u64,
+                a.max_samples as u64,
+            ],
+            None => [0; 5],
+        };
+        let settings = [
+            self.width as u64,
+            self.height as u64,
+            self.samples_per_pixel as u64,
+            self.max_depth as u64,
+            self.seed,
+            self.sampler as u64,
+            self.filter.kind as u64,
+            self.filter.radius.to_bits() as u64,
+            self.aovs.bits() as u64,
+            self.tile_size as u64,
+            self.tile_order as u64,
+            self.samples_per_pass.unwrap_or(self.samples_per_pixel) as u64,
+        ];
+        hash(&[hash(&settings), hash(&adaptive)])
+    }
+}
+
+/// 自适应采样参数：先为每个像素采集 `min_samples` 个样本，此后逐轮为尚未收敛的像素追加 `batch_size` 个样本，
+/// 直到全部收敛或用完 `samples_per_pixel × 像素数` 的总预算
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AdaptiveSampling {
+    /// 亮度均值 95% 置信区间的半宽与均值之比低于该值时视为收敛
+    pub threshold: Float,
+    pub min_samples: usize,
+    pub batch_size: usize,
+    /// 单个像素的样本数上限，默认不限制
+    pub max_samples: usize,
+}
+
+impl AdaptiveSampling {
+    pub fn new(threshold: Float) -> AdaptiveSampling {
+        AdaptiveSampling {
+            threshold,
+            min_samples: 16,
+            batch_size: 16,
+            max_samples: usize::MAX,
+        }
+    }
+
+    pub fn with_min_samples(mut self, min_samples: usize) -> AdaptiveSampling {
+        self.min_samples = min_samples;
+        self
+    }
+
+    pub fn with_batch_size(mut self, batch_size: usize) -> AdaptiveSampling {
+        self.batch_size = batch_size;
+        self
+    }
+
+    pub fn with_max_samples(mut self, max_samples: usize) -> AdaptiveSampling {
+        self.max_samples = max_samples;
+        self
+    }
+}
+
+/// 置信区间相对于均值计算，暗像素按该亮度计，以免几乎全黑的像素永不收敛
+const MIN_RELATIVE_LUMINANCE: Float = 0.1;
+
+/// 以 Welford 算法累计单个像素的样本亮度均值与方差
+#[derive(Clone, Copy, Debug, Default)]
+struct PixelEstimate {
+    count: usize,
+    mean: Float,
+    m2: Float,
+}
+
+impl PixelEstimate {
+    fn add(&mut self, color: Vec3) {
+        self.count += 1;
+        let y = luminance(&color);
+        let delta = y - self.mean;
+        self.mean += delta / self.count as Float;
+        self.m2 += delta * (y - self.mean);
+    }
+
+    /// 95% 置信区间的相对半宽，样本不足两个时为无穷大
+    fn error(&self) -> Float {
+        if self.count < 2 {
+            return Float::INFINITY;
+        }
+        let n = self.count as Float;
+        let variance = self.m2 / (n - 1.);
+        1.96 * (variance / n).sqrt() / self.mean.max(MIN_RELATIVE_LUMINANCE)
+    }
+}
+
+/// 逐行排列的线性 RGB 浮点像素，第一行位于图像顶部
+#[derive(Clone, Debug, PartialEq)]
+pub struct Framebuffer {
+    pub width: usize,
+    pub height: usize,
+    pub pixels: Vec<Float>,
+    /// 每个像素实际采集的样本数
+    pub sample_counts: Vec<usize>,
+    /// 启用的各辅助通道，像素排列与 `pixels` 相同
+    pub aovs: Vec<(Aov, Vec<Float>)>,
+}
+
+/// 保存帧缓冲时的输出设置
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct OutputSettings {
+    /// 仅作用于 PNG 输出，高动态范围格式保存原始的线性数值
+    pub tone_mapping: ToneMapping,
+    pub exr_precision: ExrPrecision,
+    pub exr_compression: ExrCompression,
+}
+
+impl Default for OutputSettings {
+    fn default() -> Self {
+        OutputSettings {
+            tone_mapping: ToneMapping::default(),
+            exr_precision: ExrPrecision::Float,
+            exr_compression: ExrCompression::Zip,
+        }
+    }
+}
+
+impl Framebuffer {
+    pub fn new(width: usize, height: usize) -> Framebuffer {
+        Framebuffer {
+            width,
+            height,
+            pixels: vec![0.; width * height * 3],
+            sample_counts: vec![0; width * height],
+            aovs: vec![],
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> Vec3 {
+        let i = (y * self.width + x) * 3;
+        Vec3::new(self.pixels[i], self.pixels[i + 1], self.pixels[i + 2])
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, color: Vec3) {
+        let i = (y * self.width + x) * 3;
+        self.pixels[i..i + 3].copy_from_slice(&[color.x, color.y, color.z]);
+    }
+
+    /// 色调映射后编码为逐行排列的 8 位 sRGB 像素
+    pub fn to_srgb8(&self, tone_mapping: &ToneMapping) -> Vec<u8> {
+        self.pixels
+            .chunks_exact(3)
+            .flat_map(|c| tone_mapping.to_srgb8(Vec3::new(c[0], c[1], c[2])))
+            .collect()
+    }
+
+    /// 将各像素的样本数按最大值归一化后以 viridis 色带着色，得到线性 RGB 的热力图
+    pub fn sample_heatmap(&self) -> Framebuffer {
+        const STOPS: [[u8; 3]; 5] = [
+            [68, 1, 84],
+            [59, 82, 139],
+            [33, 145, 140],
+            [94, 201, 98],
+            [253, 231, 37],
+        ];
+        let max = self.sample_counts.iter().copied().max().unwrap_or(0).max(1);
+        let mut heatmap = Framebuffer::new(self.width, self.height);
+        heatmap.sample_counts = self.sample_counts.clone();
+        for (pixel, &count) in heatmap.pixels.chunks_exact_mut(3).zip(&self.sample_counts) {
+            let t = count as Float / max as Float * (STOPS.len() - 1) as Float;
+            let i = (t as usize).min(STOPS.len() - 2);
+            let f = t - i as Float;
+            for (c, value) in pixel.iter_mut().enumerate() {
+                let a = srgb_to_linear(STOPS[i][c] as Float / 255.);
+                let b = srgb_to_linear(STOPS[i + 1][c] as Float / 255.);
+                *value = a + (b - a) * f;
+            }
+        }
+        heatmap
+    }
+
+    /// 辅助通道 `aov` 的像素，未启用时为 `None`
+    pub fn aov(&self, aov: Aov) -> Option<&[Float]> {
+        self.aovs
+            .iter()
+            .find(|(a, _)| *a == aov)
+            .map(|(_, values)| values.as_slice())
+    }
+
+    /// 按扩展名选择格式保存图像；OpenEXR 将辅助通道写入同一文件，
+    /// 其它格式将各辅助通道另存为 `aov_filename` 给出的文件
+    pub fn save(&self, filename: &str, output: &OutputSettings) -> Result<(), ImageError> {
+        let (width, height) = (self.width, self.height);
+        match ImageFormat::from_path(filename) {
+            Some(ImageFormat::Png) => {
+                let pixels = self.to_srgb8(&output.tone_mapping);
+                image::write_png(filename, width, height, &pixels)?;
+                for (aov, values) in &self.aovs {
+                    let pixels = aov.to_srgb8(values);
+                    image::write_png(&aov_filename(filename, *aov), width, height, &pixels)?;
+                }
+                Ok(())
+            }
+            Some(ImageFormat::Exr) if !self.aovs.is_empty() => image::write_exr_channels(
+                filename,
+                width,
+                height,
+                &self.exr_channels(),
+                output.exr_precision,
+                output.exr_compression,
+            ),
+            Some(ImageFormat::Exr) => image::write_exr(
+                filename,
+                width,
+                height,
+                &self.pixels,
+                output.exr_precision,
+                output.exr_compression,
+            ),
+            Some(ImageFormat::Pfm) => {
+                image::write_pfm(filename, width, height, &self.pixels)?;
+                for (aov, values) in &self.aovs {
+                    image::write_pfm(&aov_filename(filename, *aov), width, height, values)?;
+                }
+                Ok(())
+            }
+            None => Err(ImageError::UnsupportedFormat(filename.to_string())),
+        }
+    }
+
+    /// 颜色存为 R、G、B 通道，辅助通道加上名称前缀，深度为单独的 Z 通道，
+    /// 物体编号为单独的 32 位无符号整数 id 通道
+    fn exr_channels(&self) -> Vec<(String, ExrSamples)> {
+        let plane =
+            |values: &[Float], c: usize| values.iter().skip(c).step_by(3).copied().collect();
+        let mut channels: Vec<(String, ExrSamples)> = ["R", "G", "B"]
+            .iter()
+            .enumerate()
+            .map(|(c, name)| (name.to_string(), ExrSamples::Float(plane(&self.pixels, c))))
+            .collect();
+        for (aov, values) in &self.aovs {
+            match aov {
+                Aov::Depth => channels.push(("Z".to_string(), ExrSamples::Float(plane(values, 0)))),
+                Aov::ObjectId => {
+                    let ids = values.iter().step_by(3).map(|&id| id as u32).collect();
+                    channels.push((aov.name().to_string(), ExrSamples::Uint(ids)));
+                }
+                _ => {
+                    for (c, name) in ["R", "G", "B"].iter().enumerate() {
+                        channels.push((
+                            format!("{}.{}", aov.name(), name),
+                            ExrSamples::Float(plane(values, c)),
+                        ));
+                    }
+                }
+            }
+        }
+        channels
+    }
+}
+
+type ProgressFn = Box<dyn Fn(usize, usize) + Send + Sync>;
+type CancelFn = Box<dyn Fn() -> bool + Send + Sync>;
+type PreviewFn = Box<dyn Fn(&Framebuffer) + Send + Sync>;
+type CheckpointFn = Box<dyn Fn(&Checkpoint) + Send + Sync>;
+
+/// 以给定的摄影机、场景与着色函数渲染图像，在 Rayon 的全局线程池中按分块并行
+pub struct Renderer {
+    camera: Arc<dyn Camera>,
+    world: World,
+    ray_color: RayColorFn,
+    settings: RenderSettings,
+    progress: Option<ProgressFn>,
+    cancel: Option<CancelFn>,
+    preview: Option<PreviewFn>,
+    scene_hash: u64,
+    checkpoint: Option<CheckpointFn>,
+    resume: Option<Checkpoint>,
+}
+
+impl Renderer {
+    pub fn new(
+        camera: Arc<dyn Camera>,
+        world: World,
+        ray_color: RayColorFn,
+        settings: RenderSettings,
+    ) -> Renderer {
+        Renderer {
+            camera,
+            world,
+            ray_color,
+            settings,
+            progress: None,
+            cancel: None,
+            preview: None,
+            scene_hash: 0,
+            checkpoint: None,
+            resume: None,
+        }
+    }
+
+    /// 报告 `progress(已完成量, 总量)`：均匀采样时每完成一个分块调用一次，总量为分块数乘以轮数，
+    /// 可能来自任意工作线程；自适应采样时在每轮结束后以已用样本数与总预算调用
+    pub fn with_progress<F>(mut self, progress: F) -> Renderer
+    where
+        F: Fn(usize, usize) + Send + Sync + 'static,
+    {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+
+    /// 每个分块开始前调用 `cancel`，返回真时放弃渲染
+    pub fn with_cancel<F>(mut self, cancel: F) -> Renderer
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.cancel = Some(Box::new(cancel));
+        self
+    }
+
+    /// 渐进式渲染的每一轮或自适应采样的每一轮结束后，以目前为止的结果调用 `preview`
+    pub fn with_preview<F>(mut self, preview: F) -> Renderer
+    where
+        F: Fn(&Framebuffer) + Send + Sync + 'static,
+    {
+        self.preview = Some(Box::new(preview));
+        self
+    }
+
+    /// 场景的标识，与渲染参数一起决定检查点能否恢复，须在 `resume_from` 之前设置
+    pub fn with_scene_hash(mut self, scene_hash: u64) -> Renderer {
+        self.scene_hash = scene_hash;
+        self
+    }
+
+    /// 每一轮结束后以当前的累加状态调用 `checkpoint`，通常用于保存检查点文件
+    pub fn with_checkpoint<F>(mut self, checkpoint: F) -> Renderer
+    where
+        F: Fn(&Checkpoint) + Send + Sync + 'static,
+    {
+        self.checkpoint = Some(Box::new(checkpoint));
+        self
+    }
+
+    /// 从检查点继续渲染，检查点由不同的场景或渲染参数生成时返回 `CheckpointError::Mismatch`
+    pub fn resume_from(mut self, checkpoint: Checkpoint) -> Result<Renderer, CheckpointError> {
+        let RenderSettings {
+            width,
+            height,
+            adaptive,
+            ..
+        } = self.settings;
+        let matches = checkpoint.fingerprint == self.fingerprint()
+            && (checkpoint.width, checkpoint.height) == (width, height)
+            && checkpoint.pixels.len() == width * height
+            && checkpoint.aovs.len() == width * height * self.settings.aovs.len()
+            // 均匀采样时所有像素的样本数相同
+            && (adaptive.is_some()
+                || checkpoint.pixels.iter().all(|p| p.count == checkpoint.pixels[0].count));
+        if !matches {
+            return Err(CheckpointError::Mismatch);
+        }
+        self.resume = Some(checkpoint);
+        Ok(self)
+    }
+
+    /// 场景标识与渲染参数的哈希
+    pub fn fingerprint(&self) -> u64 {
+        hash(&[self.scene_hash, self.settings.fingerprint()])
+    }
+
+    pub fn settings(&self) -> &RenderSettings {
+        &self.settings
+    }
+
+    pub fn world(&self) -> &World {
+        &self.world
+    }
+
+    /// 渲染整幅图像，被取消时返回 `None`
+    pub fn render(&self) -> Option<Framebuffer> {
+        if let Some(adaptive) = self.settings.adaptive {
+            return self.render_adaptive(&adaptive);
+        }
+        let RenderSettings {
+            width,
+            height,
+            samples_per_pixel,
+            samples_per_pass,
+            ..
+        } = self.settings;
+        let tiles = self.tiles();
+        let mut film = self.film();
+        let mut start = 0;
+        if let Some(checkpoint) = &self.resume {
+            self.restore_film(&mut film, checkpoint);
+            start = checkpoint.pixels[0].count.min(samples_per_pixel);
+        }
+        let samples_per_pass = samples_per_pass.unwrap_or(samples_per_pixel).max(1);
+        let passes: Vec<_> = (start..samples_per_pixel)
+            .step_by(samples_per_pass)
+            .map(|start| start..(start + samples_per_pass).min(samples_per_pixel))
+            .collect();
+        let total = tiles.len() * passes.len();
+        let completed = AtomicUsize::new(0);
+        let cancelled = AtomicBool::new(false);
+        let mut states = vec![(); tiles.len()];
+        for (i, pass) in passes.iter().enumerate() {
+            let finished = self.render_tiles(
+                &mut film,
+                &tiles,
+                &mut states,
+                &cancelled,
+                |tile, _, film| {
+                    for (x, y) in tile.pixels() {
+                        self.sample_pixel(x, y, pass.clone(), |fx, fy, values| {
+                            film.add_sample(fx, fy, values)
+                        });
+                    }
+                    let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+                    if let Some(progress) = &self.progress {
+                        progress(done, total);
+                    }
+                },
+            );
+            if !finished {
+                return None;
+            }
+            if let Some(preview) = &self.preview {
+                if i + 1 < passes.len() {
+                    let mut framebuffer = self.develop(&film);
+                    framebuffer.sample_counts.fill(pass.end);
+                    preview(&framebuffer);
+                }
+            }
+            if let Some(checkpoint) = &self.checkpoint {
+                let estimates = vec![
+                    PixelEstimate {
+                        count: pass.end,
+                        ..Default::default()
+                    };
+                    width * height
+                ];
+                checkpoint(&self.make_checkpoint(&film, &estimates, pass.end * width * height));
+            }
+        }
+        let mut framebuffer = self.develop(&film);
+        framebuffer.sample_counts.fill(samples_per_pixel);
+        Some(framebuffer)
+    }
+
+    fn render_adaptive(&self, adaptive: &AdaptiveSampling) -> Option<Framebuffer> {
+        let RenderSettings {
+            width,
+            height,
+            samples_per_pixel,
+            ..
+        } = self.settings;
+        let budget = samples_per_pixel * width * height;
+        let max_samples = adaptive.max_samples.max(1);
+        let batch_size = adaptive.batch_size.max(1);
+        let tiles = self.tiles();
+        let mut film = self.film();
+        // 按分块存放各像素的估计值，分块内逐行排列
+        let mut estimates: Vec<Vec<PixelEstimate>> = tiles
+            .iter()
+            .map(|tile| vec![PixelEstimate::default(); tile.area()])
+            .collect();
+        let mut spent = 0;
+        if let Some(checkpoint) = &self.resume {
+            self.restore_film(&mut film, checkpoint);
+            for (tile, estimates) in tiles.iter().zip(estimates.iter_mut()) {
+                for ((x, y), estimate) in tile.pixels().zip(estimates.iter_mut()) {
+                    let pixel = &checkpoint.pixels[y * width + x];
+                    *estimate = PixelEstimate {
+                        count: pixel.count,
+                        mean: pixel.mean,
+                        m2: pixel.m2,
+                    };
+                }
+            }
+            spent = checkpoint.spent.min(budget);
+        }
+        let cancelled = AtomicBool::new(false);
+        loop {
+            // 每轮先确定各像素追加的样本数，预算不足时优先照顾误差最大的像素
+            let mut requests: Vec<((usize, usize), usize)> = estimates
+                .iter()
+                .enumerate()
+                .flat_map(|(t, tile)| tile.iter().enumerate().map(move |(i, e)| ((t, i), e)))
+                .filter_map(|(key, e)| {
+                    let count = if e.count == 0 {
+                        adaptive.min_samples.clamp(1, samples_per_pixel.max(1))
+                    } else if e.error() > adaptive.threshold {
+                        batch_size
+                    } else {
+                        0
+                    };
+                    let count = count.min(max_samples.saturating_sub(e.count));
+                    (count > 0).then_some((key, count))
+                })
+                .collect();
+            let requested: usize = requests.iter().map(|&(_, count)| count).sum();
+            if requested > budget - spent {
+                requests.sort_by(|&((ta, ia), _), &((tb, ib), _)| {
+                    estimates[tb][ib]
+                        .error()
+                        .total_cmp(&estimates[ta][ia].error())
+                });
+                let mut remaining = budget - spent;
+                requests.retain(|&(_, count)| {
+                    let keep = count <= remaining;
+                    if keep {
+                        remaining -= count;
+                    }
+                    keep
+                });
+            }
+            if requests.is_empty() {
+                break;
+            }
+            let mut counts: Vec<Vec<usize>> =
+                tiles.iter().map(|tile| vec![0; tile.area()]).collect();
+            for &((t, i), count) in &requests {
+                counts[t][i] = count;
+            }
+            spent += requests.iter().map(|&(_, count)| count).sum::<usize>();
+
+            let mut states: Vec<_> = estimates.iter_mut().zip(&counts).collect();
+            let finished = self.render_tiles(
+                &mut film,
+                &tiles,
+                &mut states,
+                &cancelled,
+                |tile, (estimates, counts), film| {
+                    for ((x, y), (estimate, &count)) in
+                        tile.pixels().zip(estimates.iter_mut().zip(counts.iter()))
+                    {
+                        let start = estimate.count;
+                        self.sample_pixel(x, y, start..start + count, |fx, fy, values| {
+                            estimate.add(values[0]);
+                            film.add_sample(fx, fy, values);
+                        });
+                    }
+                },
+            );
+            if !finished {
+                return None;
+            }
+            if let Some(progress) = &self.progress {
+                progress(spent, budget);
+            }
+            if let Some(preview) = &self.preview {
+                preview(&self.develop_adaptive(&film, &tiles, &estimates));
+            }
+            if let Some(checkpoint) = &self.checkpoint {
+                let estimates = self.image_order(&tiles, &estimates);
+                checkpoint(&self.make_checkpoint(&film, &estimates, spent));
+            }
+        }
+        Some(self.develop_adaptive(&film, &tiles, &estimates))
+    }
+
+    /// 颜色之后依次为各辅助通道的胶片
+    fn film(&self) -> Film {
+        let RenderSettings {
+            width,
+            height,
+            filter,
+            aovs,
+            ..
+        } = self.settings;
+        let film = Film::with_layers(width, height, filter, 1 + aovs.len());
+        // 物体编号不能混合，只保留像素内第一个样本的取值
+        aovs.iter()
+            .enumerate()
+            .filter(|(_, aov)| *aov == Aov::ObjectId)
+            .fold(film, |film, (layer, _)| film.with_first_sample(1 + layer))
+    }
+
+    fn restore_film(&self, film: &mut Film, checkpoint: &Checkpoint) {
+        let width = self.settings.width;
+        let count = self.settings.aovs.len();
+        let mut sums = vec![Vec3::zeros(); 1 + count];
+        for (i, pixel) in checkpoint.pixels.iter().enumerate() {
+            sums[0] = pixel.sum;
+            sums[1..].copy_from_slice(&checkpoint.aovs[i * count..(i + 1) * count]);
+            film.set_accumulation(i % width, i / width, &sums, pixel.weight, pixel.count);
+        }
+    }
+
+    /// `estimates` 为逐行排列的各像素估计值
+    fn make_checkpoint(
+        &self,
+        film: &Film,
+        estimates: &[PixelEstimate],
+        spent: usize,
+    ) -> Checkpoint {
+        let RenderSettings { width, height, .. } = self.settings;
+        let mut aovs = Vec::with_capacity(width * height * self.settings.aovs.len());
+        let pixels = estimates
+            .iter()
+            .enumerate()
+            .map(|(i, estimate)| {
+                let (sums, weight) = film.accumulation(i % width, i / width);
+                aovs.extend_from_slice(&sums[1..]);
+                PixelState {
+                    sum: sums[0],
+                    weight,
+                    count: estimate.count,
+                    mean: estimate.mean,
+                    m2: estimate.m2,
+                }
+            })
+            .collect();
+        Checkpoint {
+            fingerprint: self.fingerprint(),
+            width,
+            height,
+            spent,
+            pixels,
+            aovs,
+        }
+    }
+
+    /// 将按分块存放的估计值重新逐行排列
+    fn image_order(&self, tiles: &[Tile], estimates: &[Vec<PixelEstimate>]) -> Vec<PixelEstimate> {
+        let RenderSettings { width, height, .. } = self.settings;
+        let mut result = vec![PixelEstimate::default(); width * height];
+        for (tile, estimates) in tiles.iter().zip(estimates) {
+            for ((x, y), estimate) in tile.pixels().zip(estimates) {
+                result[y * width + x] = *estimate;
+            }
+        }
+        result
+    }
+
+    fn tiles(&self) -> Vec<Tile> {
+        let RenderSettings {
+            width,
+            height,
+            tile_size,
+            tile_order,
+            ..
+        } = self.settings;
+        tile::tiles(width, height, tile_size, tile_order)
+    }
+
+    /// 按顺序每次取出若干分块并行渲染，每个分块写入独立的胶片区域后按分块顺序合并，
+    /// 因此滤波器跨分块累加的结果同样与线程数无关；半径超过半个像素的滤波器在分块边缘处的累加顺序
+    /// 取决于分块的大小与顺序，二者改变时结果会有末位差异。`states` 为各分块的状态，被取消时返回假
+    fn render_tiles<T, F>(
+        &self,
+        film: &mut Film,
+        tiles: &[Tile],
+        states: &mut [T],
+        cancelled: &AtomicBool,
+        render_tile: F,
+    ) -> bool
+    where
+        T: Send,
+        F: Fn(&Tile, &mut T, &mut FilmTile) + Sync,
+    {
+        let batch = rayon::current_num_threads() * 4;
+        for (tiles, states) in tiles.chunks(batch).zip(states.chunks_mut(batch)) {
+            let results: Vec<FilmTile> = tiles
+                .par_iter()
+                .zip(states.par_iter_mut())
+                .map(|(tile, state)| {
+                    let mut film_tile = film.tile(tile);
+                    if !self.is_cancelled(cancelled) {
+                        render_tile(tile, state, &mut film_tile);
+                    }
+                    film_tile
+                })
+                .collect();
+            if cancelled.load(Ordering::Relaxed) {
+                return false;
+            }
+            for film_tile in &results {
+                film.merge(film_tile);
+            }
+        }
+        true
+    }
+
+    fn develop(&self, film: &Film) -> Framebuffer {
+        let RenderSettings {
+            width,
+            height,
+            aovs,
+            ..
+        } = self.settings;
+        let mut framebuffer = Framebuffer::new(width, height);
+        for y in 0..height {
+            for x in 0..width {
+                // 保留线性的高动态范围数值
+                framebuffer.set(x, y, film.color(x, y));
+            }
+        }
+        for (layer, aov) in aovs.iter().enumerate() {
+            let values = (0..height)
+                .flat_map(|y| (0..width).map(move |x| (x, y)))
+                .flat_map(|(x, y)| {
+                    let value = film.layer(x, y, layer + 1);
+                    [value.x, value.y, value.z]
+                })
+                .collect();
+            framebuffer.aovs.push((aov, values));
+        }
+        framebuffer
+    }
+
+    fn develop_adaptive(
+        &self,
+        film: &Film,
+        tiles: &[Tile],
+        estimates: &[Vec<PixelEstimate>],
+    ) -> Framebuffer {
+        let mut framebuffer = self.develop(film);
+        for (count, estimate) in framebuffer
+            .sample_counts
+            .iter_mut()
+            .zip(self.image_order(tiles, estimates))
+        {
+            *count = estimate.count;
+        }
+        framebuffer
+    }
+
+    fn is_cancelled(&self, cancelled: &AtomicBool) -> bool {
+        if cancelled.load(Ordering::Relaxed) || self.cancel.as_ref().is_some_and(|f| f()) {
+            cancelled.store(true, Ordering::Relaxed);
+            return true;
+        }
+        false
+    }
+
+    /// 依次采集像素 (x, y) 序号位于 `indices` 中的样本，连同样本的胶片坐标交给 `add`；
+    /// 每个样本的取值依次为颜色与各辅助通道
+    fn sample_pixel<F>(&self, x: usize, y: usize, indices: std::ops::Range<usize>, mut add: F)
+    where
+        F: FnMut(Float, Float, &[Vec3]),
+    {
+        let RenderSettings {
+            width,
+            height,
+            samples_per_pixel,
+            max_depth,
+            seed,
+            sampler,
+            aovs,
+            ..
+        } = self.settings;
+        let mut sampler = sampler.create(seed, x, y, samples_per_pixel);
+        let mut values = vec![Vec3::zeros(); 1 + aovs.len()];
+        let forward = self.camera.forward();
+        for index in indices {
+            sampler.start_sample(index);
+            let fx = x as Float + sampler.random();
+            let fy = y as Float + sampler.random();
+            // 胶片坐标按图像宽高归一化，像素 (x, y) 的中心对应 ((x + 0.5) / width, ...)
+            let u = fx / width as Float;
+            let v = 1. - fy / height as Float;
+            let ray = self.camera.ray(u, v, sampler.as_mut());
+            // 辅助通道取自着色函数求得的首个交点，不消耗随机数，颜色不受影响
+            let (color, rec) = (self.ray_color)(&ray, &self.world, max_depth, sampler.as_mut());
+            values[0] = color;
+            for (value, aov) in values[1..].iter_mut().zip(aovs.iter()) {
+                *value = aov.evaluate(&ray, rec.as_ref(), &self.world, &forward);
+            }
+            add(fx, fy, &values);
+        }
+    }
+}
+
+/// 以默认宽高比与种子创建内置场景的渲染器
+#[cfg(test)]
+fn scene_renderer(scene: crate::scene::Scene, settings: RenderSettings) -> Renderer {
+    let (camera, world, ray_color) = crate::scene::create_scene(scene, 1.5, true, 0);
+    Renderer::new(camera, world, ray_color, settings)
+}
+
+/// 在给定线程数的线程池中渲染，用于检验结果与线程数无关
+#[cfg(test)]
+fn render_with_threads(renderer: &Renderer, num_threads: usize) -> Framebuffer {
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(num_threads)
+        .build()
+        .unwrap();
+    pool.install(|| renderer.render().unwrap())
+}
+
+#[test]
+fn test_renderer() {
+    use crate::scene::Scene;
+
+    let settings = RenderSettings::new(12, 8)
+        .with_samples_per_pixel(4)
+        .with_max_depth(8)
+        .with_seed(7)
+        .with_tile_size(4);
+    let tiles = Arc::new(AtomicUsize::new(0));
+    let counter = tiles.clone();
+    let renderer =
+        scene_renderer(Scene::DiffuseSpheres, settings).with_progress(move |_, total| {
+            assert_eq!(total, 6);
+            counter.fetch_add(1, Ordering::Relaxed);
+        });
+    let first = renderer.render().unwrap();
+    assert_eq!(tiles.load(Ordering::Relaxed), 6);
+    assert_eq!(first.pixels.len(), 12 * 8 * 3);
+    assert!(first.pixels.iter().all(|c| c.is_finite() && *c >= 0.));
+    // 随机数只取决于种子与样本，重复渲染或改变线程数的结果完全一致
+    assert_eq!(renderer.render().unwrap(), first);
+    for num_threads in [1, 4] {
+        assert_eq!(render_with_threads(&renderer, num_threads), first);
+    }
+
+    let renderer = scene_renderer(Scene::DiffuseSpheres, settings).with_cancel(|| true);
+    assert!(renderer.render().is_none());
+
+    // 只有一行或一列像素的图像同样有效
+    for (width, height) in [(1, 1), (1, 4), (4, 1)] {
+        let settings = RenderSettings::new(width, height)
+            .with_samples_per_pixel(2)
+            .with_max_depth(4);
+        let framebuffer = scene_renderer(Scene::DiffuseSpheres, settings)
+            .render()
+            .unwrap();
+        assert!(framebuffer.pixels.iter().all(|c| c.is_finite() && *c >= 0.));
+    }
+}
+
+#[test]
+fn test_bvh_render_matches_linear_scan() {
+    use crate::scene::{create_scene, Scene};
+
+    // 参与介质不从采样器中取随机数，跳过包围盒未命中的介质不会改变后续样本
+    let settings = RenderSettings::new(16, 16)
+        .with_samples_per_pixel(4)
+        .with_max_depth(8);
+    let render = |use_bvh| {
+        let (camera, world, ray_color) = create_scene(Scene::CornellSmoke, 1., use_bvh, 0);
+        Renderer::new(camera, world, ray_color, settings)
+            .render()
+            .unwrap()
+    };
+    assert_eq!(render(true), render(false));
+}
+
+#[test]
+fn test_progressive_render() {
+    use crate::scene::Scene;
+
+    let settings = RenderSettings::new(12, 8)
+        .with_samples_per_pixel(4)
+        .with_max_depth(8)
+        .with_tile_size(5)
+        .with_tile_order(TileOrder::Hilbert);
+    let expected = scene_renderer(Scene::DiffuseSpheres, settings)
+        .render()
+        .unwrap();
+
+    // 每轮一个样本时各像素的累加顺序不变，结果与一次采完逐位相同
+    let previews = Arc::new(std::sync::Mutex::new(vec![]));
+    let recorder = previews.clone();
+    let renderer = scene_renderer(Scene::DiffuseSpheres, settings.with_samples_per_pass(1))
+        .with_preview(move |framebuffer| {
+            recorder.lock().unwrap().push(framebuffer.sample_counts[0]);
+        });
+    assert_eq!(renderer.render().unwrap(), expected);
+    assert_eq!(*previews.lock().unwrap(), vec![1, 2, 3]);
+}
+
+#[test]
+fn test_adaptive_sampling() {
+    use crate::scene::Scene;
+
+    let settings = RenderSettings::new(24, 16)
+        .with_samples_per_pixel(32)
+        .with_max_depth(8)
+        .with_adaptive(AdaptiveSampling::new(0.05).with_min_samples(8));
+    let renderer = scene_renderer(Scene::Weekend, settings);
+    let framebuffer = renderer.render().unwrap();
+    let counts = &framebuffer.sample_counts;
+    assert!(counts.iter().sum::<usize>() <= 24 * 16 * 32);
+    assert!(counts.iter().all(|&n| n >= 8));
+    // 平坦的天空提前收敛，样本集中在噪声较大的像素上
+    let sky = counts[..24].iter().sum::<usize>() / 24;
+    let max = *counts.iter().max().unwrap();
+    assert!(sky < 32 && max > 32, "sky {} max {}", sky, max);
+    assert!(framebuffer.pixels.iter().all(|c| c.is_finite() && *c >= 0.));
+
+    for num_threads in [1, 4] {
+        assert_eq!(render_with_threads(&renderer, num_threads), framebuffer);
+    }
+
+    let heatmap = framebuffer.sample_heatmap();
+    let brightest = counts.iter().position(|&n| n == max).unwrap();
+    assert!(
+        luminance(&heatmap.get(brightest % 24, brightest / 24)) > luminance(&heatmap.get(0, 0))
+    );
+}
+
+#[test]
+fn test_filtered_render() {
+    use crate::film::FilterKind;
+    use crate::scene::Scene;
+
+    let settings = RenderSettings::new(12, 8)
+        .with_samples_per_pixel(4)
+        .with_max_depth(8)
+        .with_filter(Filter::new(FilterKind::Mitchell));
+    let renderer = scene_renderer(Scene::DiffuseSpheres, settings);
+    let first = renderer.render().unwrap();
+    assert!(first.pixels.iter().all(|c| c.is_finite()));
+    // 跨行累加的滤波结果同样与线程数无关
+    for num_threads in [1, 4] {
+        assert_eq!(render_with_threads(&renderer, num_threads), first);
+    }
+
+    // 默认的盒形滤波器只累加到样本所在的像素，结果与分块方式无关
+    let box_filtered = |tile_size, tile_order| {
+        let settings = settings
+            .with_filter(Filter::default())
+            .with_tile_size(tile_size)
+            .with_tile_order(tile_order);
+        scene_renderer(Scene::DiffuseSpheres, settings)
+            .render()
+            .unwrap()
+    };
+    let expected = box_filtered(16, TileOrder::Spiral);
+    assert_eq!(box_filtered(3, TileOrder::Hilbert), expected);
+    assert_eq!(box_filtered(5, TileOrder::Scanline), expected);
+}
+
+#[test]
+fn test_checkpoint_resume() {
+    use crate::scene::Scene;
+    use std::sync::Mutex;
+
+    for adaptive in [false, true] {
+        let mut settings = RenderSettings::new(12, 8)
+            .with_samples_per_pixel(6)
+            .with_max_depth(8)
+            .with_samples_per_pass(2);
+        if adaptive {
+            settings = settings.with_adaptive(
+                AdaptiveSampling::new(0.1)
+                    .with_min_samples(2)
+                    .with_batch_size(2),
+            );
+        }
+        let renderer = || scene_renderer(Scene::DiffuseSpheres, settings).with_scene_hash(1);
+        let checkpoints = Arc::new(Mutex::new(vec![]));
+        let recorder = checkpoints.clone();
+        let expected = renderer()
+            .with_checkpoint(move |checkpoint| recorder.lock().unwrap().push(checkpoint.clone()))
+            .render()
+            .unwrap();
+        let checkpoints = checkpoints.lock().unwrap();
+        assert!(checkpoints.len() >= 2);
+
+        // 从第一轮后的检查点继续渲染，结果与一次渲染完成逐位相同
+        let resumed = renderer()
+            .resume_from(checkpoints[0].clone())
+            .unwrap()
+            .render()
+            .unwrap();
+        assert_eq!(resumed, expected);
+
+        // 场景或参数改变后拒绝恢复
+        let changed = scene_renderer(Scene::DiffuseSpheres, settings.with_seed(1))
+            .with_scene_hash(1)
+            .resume_from(checkpoints[0].clone());
+        assert!(matches!(changed, Err(CheckpointError::Mismatch)));
+        let changed = renderer()
+            .with_scene_hash(2)
+            .resume_from(checkpoints[0].clone());
+        assert!(matches!(changed, Err(CheckpointError::Mismatch)));
+        // 每轮样本数与分块改变累加顺序，同样拒绝恢复
+        for changed in [
+            settings.with_samples_per_pass(3),
+            settings.with_tile_size(4),
+            settings.with_tile_order(TileOrder::Hilbert),
+        ] {
+            let changed = scene_renderer(Scene::DiffuseSpheres, changed)
+                .with_scene_hash(1)
+                .resume_from(checkpoints[0].clone());
+            assert!(matches!(changed, Err(CheckpointError::Mismatch)));
+        }
+    }
+}
+
+#[test]
+fn test_aovs() {
+    use crate::scene::Scene;
+    use std::sync::Mutex;
+
+    let renderer = |settings| scene_renderer(Scene::ShinyMetal, settings);
+    let settings = RenderSettings::new(24, 16)
+        .with_samples_per_pixel(4)
+        .with_max_depth(8)
+        .with_samples_per_pass(2);
+    let aovs: AovSet = Aov::ALL.into_iter().collect();
+    let checkpoints = Arc::new(Mutex::new(vec![]));
+    let recorder = checkpoints.clone();
+    let framebuffer = renderer(settings.with_aovs(aovs))
+        .with_checkpoint(move |checkpoint| recorder.lock().unwrap().push(checkpoint.clone()))
+        .render()
+        .unwrap();
+    // 辅助通道不消耗随机数，颜色与不输出辅助通道时逐位相同
+    assert_eq!(
+        framebuffer.pixels,
+        renderer(settings).render().unwrap().pixels
+    );
+    assert_eq!(framebuffer.aovs.len(), 5);
+
+    let at = |aov, x: usize, y: usize| {
+        let values = framebuffer.aov(aov).unwrap();
+        let i = (y * 24 + x) * 3;
+        Vec3::new(values[i], values[i + 1], values[i + 2])
+    };
+    // 图像中心是位于 z = -1 处、半径 0.5 的漫反射球
+    let albedo = at(Aov::Albedo, 12, 8);
+    assert!((albedo - Vec3::new(0.7, 0.3, 0.3)).length() < 1e-4);
+    assert!(at(Aov::Normal, 12, 8).z > 0.95);
+    assert!((at(Aov::Depth, 12, 8).x - 0.5).abs() < 0.05);
+    assert!((at(Aov::Position, 12, 8).z + 0.5).abs() < 0.05);
+    // 顶部只有天空
+    assert_eq!(at(Aov::ObjectId, 12, 0), Vec3::zeros());
+    assert_eq!(at(Aov::Depth, 12, 0), Vec3::zeros());
+    assert!(at(Aov::Albedo, 12, 0).z > 0.9);
+    assert_ne!(at(Aov::ObjectId, 12, 8), at(Aov::ObjectId, 12, 15));
+
+    // 辅助通道随检查点保存，恢复后的结果同样不变
+    let checkpoint = checkpoints.lock().unwrap()[0].clone();
+    assert_eq!(checkpoint.aovs.len(), 24 * 16 * 5);
+    let resumed = renderer(settings.with_aovs(aovs))
+        .resume_from(checkpoint)
+        .unwrap()
+        .render()
+        .unwrap();
+    assert_eq!(resumed, framebuffer);
+
+    // 物体编号不经滤波也不取平均，Mitchell 滤波时每个像素仍是确切的编号：
+    // 地面与三个球的序号加一，或未击中时的 0
+    let framebuffer = renderer(
+        settings
+            .with_filter(Filter::new(crate::film::FilterKind::Mitchell))
+            .with_aovs([Aov::ObjectId].into_iter().collect()),
+    )
+    .render()
+    .unwrap();
+    let ids = framebuffer.aov(Aov::ObjectId).unwrap();
+    assert!(ids.iter().all(|&id| [0., 1., 2., 3., 4.].contains(&id)));
+    assert_eq!(ids[(8 * 24 + 12) * 3], 2.);
+}
@@ -0,0 +1,484 @@
+//! 显式传递的采样器，渲染结果只取决于种子，与线程数及任务划分方式无关
+//!
+//! 每个像素创建一个采样器，逐个样本调用 `start_sample` 后依次取用各维的值。
+//! 像素抖动、镜头、时间与各次散射按调用顺序占用不同的维度，
+//! 低差异序列因而在所有维度上都比独立的随机数分布得更均匀。
+
+use std::str::FromStr;
+
+use crate::math::Float;
+
+pub trait Sampler {
+    /// 开始当前像素的第 `index` 个样本，此后的取值自第一维开始
+    fn start_sample(&mut self, index: usize);
+
+    /// 当前样本下一维上的值，属于 [0, 1)
+    fn random(&mut self) -> Float;
+
+    fn random_between(&mut self, min: Float, max: Float) -> Float {
+        min + (max - min) * self.random()
+    }
+
+    /// [0, n) 内均匀分布的下标
+    fn random_index(&mut self, n: usize) -> usize {
+        ((self.random() * n as Float) as usize).min(n - 1)
+    }
+}
+
+/// 采样器的种类
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SamplerKind {
+    /// 各维独立的均匀随机数
+    Random,
+    /// 相关多重抖动（correlated multi-jittered），每两维构成二维分层
+    Stratified,
+    /// 以 Cranley-Patterson 旋转打乱的 Halton 序列
+    Halton,
+    /// 以哈希实现 Owen 置乱的 Sobol 序列
+    Sobol,
+}
+
+impl FromStr for SamplerKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "random" => Ok(SamplerKind::Random),
+            "stratified" => Ok(SamplerKind::Stratified),
+            "halton" => Ok(SamplerKind::Halton),
+            "sobol" => Ok(SamplerKind::Sobol),
+            _ => Err(format!("Unknown sampler {}", s)),
+        }
+    }
+}
+
+impl SamplerKind {
+    /// 第 `(x, y)` 个像素的采样器，`samples_per_pixel` 为该像素的样本总数
+    pub fn create(
+        &self,
+        seed: u64,
+        x: usize,
+        y: usize,
+        samples_per_pixel: usize,
+    ) -> Box<dyn Sampler> {
+        let seed = hash(&[seed, x as u64, y as u64]);
+        match self {
+            SamplerKind::Random => Box::new(RandomSampler::new(seed)),
+            SamplerKind::Stratified => Box::new(StratifiedSampler::new(seed, samples_per_pixel)),
+            SamplerKind::Halton => Box::new(HaltonSampler::new(seed)),
+            SamplerKind::Sobol => Box::new(SobolSampler::new(seed)),
+        }
+    }
+}
+
+/// 由种子确定的伪随机数序列
+#[derive(Clone, Debug)]
+pub struct RandomSampler {
+    seed: u64,
+    rng: fastrand::Rng,
+}
+
+impl RandomSampler {
+    pub fn new(seed: u64) -> RandomSampler {
+        RandomSampler {
+            seed,
+            rng: fastrand::Rng::with_seed(seed),
+        }
+    }
+}
+
+impl Sampler for RandomSampler {
+    fn start_sample(&mut self, index: usize) {
+        self.rng = fastrand::Rng::with_seed(hash(&[self.seed, index as u64]));
+    }
+
+    #[cfg(feature = "single-precision")]
+    fn random(&mut self) -> Float {
+        self.rng.f32()
+    }
+
+    #[cfg(not(feature = "single-precision"))]
+    fn random(&mut self) -> Float {
+        self.rng.f64()
+    }
+
+    fn random_index(&mut self, n: usize) -> usize {
+        self.rng.usize(..n)
+    }
+}
+
+/// 将 `samples_per_pixel` 个样本排成近似正方形的 m × n 网格，每两维按 Kensler 的相关多重抖动分层，
+/// 每一维单独投影时也恰好各占一层
+pub struct StratifiedSampler {
+    seed: u32,
+    samples_per_pixel: u32,
+    m: u32,
+    n: u32,
+    index: u32,
+    dimension: u32,
+    /// 二维样本中尚未取用的第二个分量
+    pending: Option<Float>,
+}
+
+impl StratifiedSampler {
+    pub fn new(seed: u64, samples_per_pixel: usize) -> StratifiedSampler {
+        let samples_per_pixel = samples_per_pixel.max(1) as u32;
+        let m = ((samples_per_pixel as f64).sqrt().round() as u32).max(1);
+        let n = samples_per_pixel.div_ceil(m);
+        StratifiedSampler {
+            seed: seed as u32,
+            samples_per_pixel,
+            m,
+            n,
+            index: 0,
+            dimension: 0,
+            pending: None,
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn start_sample(&mut self, index: usize) {
+        self.index = index as u32;
+        self.dimension = 0;
+        self.pending = None;
+    }
+
+    fn random(&mut self) -> Float {
+        if let Some(value) = self.pending.take() {
+            return value;
+        }
+        let pattern = self.seed ^ self.dimension.wrapping_mul(0x9e3779b9);
+        self.dimension += 1;
+        let (m, n) = (self.m, self.n);
+        // 样本数超过预定值时各组重复分层
+        let s = permute(
+            self.index % self.samples_per_pixel,
+            self.samples_per_pixel,
+            pattern.wrapping_mul(0x51633e2d),
+        );
+        let sx = permute(s % m, m, pattern.wrapping_mul(0xa511e9b3));
+        let sy = permute(s / m, n, pattern.wrapping_mul(0x63d83595));
+        let jx = hash_to_unit(s, pattern.wrapping_mul(0xa399d265) ^ self.index);
+        let jy = hash_to_unit(s, pattern.wrapping_mul(0x711ad6a5) ^ self.index);
+        let x = ((s % m) as Float + (sy as Float + jx) / n as Float) / m as Float;
+        let y = ((s / m) as Float + (sx as Float + jy) / m as Float) / n as Float;
+        self.pending = Some(y.min(ONE_MINUS_EPSILON));
+        x.min(ONE_MINUS_EPSILON)
+    }
+}
+
+/// 第 d 维以第 d 个素数为底的根式逆，每个像素的每一维以不同的偏移旋转；
+/// 超出素数表的维度退化为哈希随机数
+pub struct HaltonSampler {
+    seed: u64,
+    index: u64,
+    dimension: usize,
+}
+
+impl HaltonSampler {
+    pub fn new(seed: u64) -> HaltonSampler {
+        HaltonSampler {
+            seed,
+            index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn start_sample(&mut self, index: usize) {
+        self.index = index as u64;
+        self.dimension = 0;
+    }
+
+    fn random(&mut self) -> Float {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let offset = hash(&[self.seed, dimension as u64]);
+        match PRIMES.get(dimension) {
+            Some(&base) => {
+                let shift = (offset >> 11) as f64 / (1u64 << 53) as f64;
+                let value = (radical_inverse(self.index, base) + shift).fract();
+                (value as Float).min(ONE_MINUS_EPSILON)
+            }
+            None => unit_from_bits(hash(&[offset, self.index]) as u32),
+        }
+    }
+}
+
+/// Burley 的哈希 Owen 置乱：每四维为一组共用前四维 Sobol 序列，组内以相同的置乱打乱样本次序，
+/// 各维的值再分别置乱，不同组之间因而互不相关
+pub struct SobolSampler {
+    seed: u64,
+    index: u32,
+    dimension: u32,
+}
+
+impl SobolSampler {
+    pub fn new(seed: u64) -> SobolSampler {
+        SobolSampler {
+            seed,
+            index: 0,
+            dimension: 0,
+        }
+    }
+}
+
+impl Sampler for SobolSampler {
+    fn start_sample(&mut self, index: usize) {
+        self.index = index as u32;
+        self.dimension = 0;
+    }
+
+    fn random(&mut self) -> Float {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let group_seed = hash(&[self.seed, (dimension / 4) as u64]) as u32;
+        let index = nested_uniform_scramble(self.index, group_seed);
+        let value = sobol(index, (dimension % 4) as usize);
+        let dimension_seed = hash(&[self.seed, dimension as u64, 1]) as u32;
+        unit_from_bits(nested_uniform_scramble(value, dimension_seed))
+    }
+}
+
+/// 小于 1 的最大浮点数
+const ONE_MINUS_EPSILON: Float = 1. - Float::EPSILON / 2.;
+
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131,
+];
+
+fn unit_from_bits(bits: u32) -> Float {
+    ((bits as f64 / (1u64 << 32) as f64) as Float).min(ONE_MINUS_EPSILON)
+}
+
+fn radical_inverse(mut index: u64, base: u64) -> f64 {
+    let inverse_base = 1. / base as f64;
+    let (mut reversed, mut scale) = (0., inverse_base);
+    while index > 0 {
+        reversed += (index % base) as f64 * scale;
+        index /= base;
+        scale *= inverse_base;
+    }
+    reversed
+}
+
+/// Sobol 序列前四维的生成矩阵，按位从高到低排列，第一维即 van der Corput 序列
+const SOBOL_MATRICES: [[u32; 32]; 4] = sobol_matrices();
+
+/// 由 Joe 与 Kuo 给出的本原多项式与初始方向数递推生成矩阵
+const fn sobol_matrices() -> [[u32; 32]; 4] {
+    // (次数 s, 多项式系数 a, 初始方向数 m)
+    const PARAMETERS: [(usize, u32, [u32; 3]); 3] =
+        [(1, 0, [1, 0, 0]), (2, 1, [1, 3, 0]), (3, 1, [1, 3, 1])];
+    let mut matrices = [[0; 32]; 4];
+    let mut k = 0;
+    while k < 32 {
+        matrices[0][k] = 1 << (31 - k);
+        k += 1;
+    }
+    let mut d = 0;
+    while d < 3 {
+        let (s, a, m) = PARAMETERS[d];
+        let v = &mut matrices[d + 1];
+        let mut k = 0;
+        while k < 32 {
+            v[k] = if k < s {
+                m[k] << (31 - k)
+            } else {
+                let mut value = v[k - s] ^ (v[k - s] >> s);
+                let mut j = 1;
+                while j < s {
+                    if (a >> (s - 1 - j)) & 1 == 1 {
+                        value ^= v[k - j];
+                    }
+                    j += 1;
+                }
+                value
+            };
+            k += 1;
+        }
+        d += 1;
+    }
+    matrices
+}
+
+fn sobol(mut index: u32, dimension: usize) -> u32 {
+    let matrix = &SOBOL_MATRICES[dimension];
+    let mut result = 0;
+    let mut k = 0;
+    while index != 0 {
+        if index & 1 == 1 {
+            result ^= matrix[k];
+        }
+        index >>= 1;
+        k += 1;
+    }
+    result
+}
+
+/// 对二进制位自高向低的嵌套均匀置乱，等价于以 `seed` 确定的 Owen 置乱
+fn nested_uniform_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    // Laine-Karras 置换，每一位只受更低位的影响
+    x ^= x.wrapping_mul(0x3d20adea);
+    x = x.wrapping_add(seed);
+    x = x.wrapping_mul((seed >> 16) | 1);
+    x ^= x.wrapping_mul(0x05526c56);
+    x ^= x.wrapping_mul(0x53a22864);
+    x.reverse_bits()
+}
+
+/// Kensler 的哈希置换：以 `pattern` 确定 [0, l) 上的一个排列并返回 `i` 的像
+fn permute(mut i: u32, l: u32, pattern: u32) -> u32 {
+    let p = pattern;
+    let mut w = l.wrapping_sub(1);
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dcb303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e501cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < l {
+            break;
+        }
+    }
+    (i.wrapping_add(p)) % l
+}
+
+/// Kensler 的哈希随机数，属于 [0, 1)
+fn hash_to_unit(mut i: u32, p: u32) -> Float {
+    i ^= p;
+    i ^= i >> 17;
+    i ^= i >> 10;
+    i = i.wrapping_mul(0xb36534e5);
+    i ^= i >> 12;
+    i ^= i >> 21;
+    i = i.wrapping_mul(0x93fc4795);
+    i ^= 0xdf6e307f;
+    i ^= i >> 17;
+    i = i.wrapping_mul(1 | p >> 18);
+    unit_from_bits(i)
+}
+
+/// 以 SplitMix64 的混合函数逐个合并各值
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e3779b97f4a7c15, |h, &v| {
+        let mut z = (h ^ v).wrapping_add(0x9e3779b97f4a7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    })
+}
+
+#[test]
+fn test_sampler_deterministic() {
+    let kinds = [
+        SamplerKind::Random,
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ];
+    for kind in kinds {
+        let mut a = kind.create(3, 10, 20, 16);
+        let mut b = kind.create(3, 10, 20, 16);
+        let values = |sampler: &mut Box<dyn Sampler>, index| {
+            sampler.start_sample(index);
+            (0..64).map(|_| sampler.random()).collect::<Vec<_>>()
+        };
+        let first = values(&mut a, 5);
+        assert_eq!(first, values(&mut b, 5), "{:?}", kind);
+        assert_ne!(first, values(&mut b, 6), "{:?}", kind);
+        assert_eq!(first, values(&mut a, 5), "{:?}", kind);
+        assert!(first.iter().all(|x| (0. ..1.).contains(x)), "{:?}", kind);
+    }
+}
+
+#[test]
+fn test_sobol_sequence() {
+    // 未置乱的第二维按自然次序（而非格雷码次序）排列
+    let expected = [0., 0.5, 0.75, 0.25, 0.625, 0.125, 0.375, 0.875];
+    for (i, e) in expected.iter().enumerate() {
+        assert_eq!(sobol(i as u32, 1) as f64 / (1u64 << 32) as f64, *e);
+    }
+    assert!((radical_inverse(5, 3) - 7. / 9.).abs() < 1e-12);
+}
+
+#[test]
+fn test_stratified_and_sobol_strata() {
+    // 16 个样本在两个维度上各自恰好落入 16 个等宽区间，二维上落入 4 × 4 网格的每一格
+    for kind in [SamplerKind::Stratified, SamplerKind::Sobol] {
+        let mut sampler = kind.create(7, 1, 2, 16);
+        let (mut xs, mut ys, mut cells) = (vec![0; 16], vec![0; 16], vec![0; 16]);
+        for index in 0..16 {
+            sampler.start_sample(index);
+            let (x, y) = (sampler.random(), sampler.random());
+            xs[(x * 16.) as usize] += 1;
+            ys[(y * 16.) as usize] += 1;
+            cells[(y * 4.) as usize * 4 + (x * 4.) as usize] += 1;
+        }
+        assert!(
+            xs.iter().chain(&ys).chain(&cells).all(|&c| c == 1),
+            "{:?}",
+            kind
+        );
+    }
+}
+
+#[test]
+fn test_low_discrepancy_rmse() {
+    use crate::render::{RenderSettings, Renderer};
+    use crate::scene::{create_scene, Scene};
+
+    // 以大量随机样本的结果为参考，低差异采样器在相同样本数下误差更小
+    let render = |kind: SamplerKind, samples: usize, seed: u64| {
+        let (camera, world, ray_color) = create_scene(Scene::Weekend, 1.5, true, 0);
+        let settings = RenderSettings::new(24, 16)
+            .with_samples_per_pixel(samples)
+            .with_max_depth(8)
+            .with_seed(seed)
+            .with_sampler(kind);
+        Renderer::new(camera, world, ray_color, settings)
+            .render()
+            .unwrap()
+            .pixels
+    };
+    let reference = render(SamplerKind::Random, 1024, 1);
+    let rmse = |pixels: Vec<Float>| {
+        let squared: f64 = pixels
+            .iter()
+            .zip(&reference)
+            .map(|(a, b)| ((a - b) as f64).powi(2))
+            .sum();
+        (squared / pixels.len() as f64).sqrt()
+    };
+    let random = rmse(render(SamplerKind::Random, 16, 2));
+    for kind in [
+        SamplerKind::Stratified,
+        SamplerKind::Halton,
+        SamplerKind::Sobol,
+    ] {
+        let error = rmse(render(kind, 16, 2));
+        assert!(error < random * 0.9, "{:?}: {} vs {}", kind, error, random);
+    }
+}
@@ -1,15 +1,37 @@
 use std::str::FromStr;
 use std::sync::Arc;
 
+use crate::bvh::Bvh;
 use crate::camera::{Camera, PositionalCamera, SimpleCamera};
-use crate::hittable::{Hittable, HittableList};
-use crate::material::{Dielectric, DummyMaterial, Lambertian, Material, Metal};
-use crate::math::{floats, random, Float};
+use crate::hittable::{HitRecord, Hittable, HittableList};
+use crate::instance::Instance;
+use crate::mat4::Mat4;
+use crate::material::{
+    Dielectric, DiffuseLight, DummyMaterial, Isotropic, Lambertian, Material, Metal,
+};
+use crate::math::Float;
+use crate::medium::ConstantMedium;
 use crate::ray::Ray;
-use crate::sphere::Sphere;
+use crate::sampler::{RandomSampler, Sampler};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::SolidColor;
+use crate::triangle::TriangleMesh;
 use crate::vec3::Vec3;
+use crate::world::{Background, World};
 
-type RayColorFn = fn(&Ray, &dyn Hittable, i32) -> Vec3;
+/// 着色函数，返回光线带回的辐射亮度及其首个交点，后者供辅助通道使用
+pub type RayColorFn = fn(&Ray, &World, i32, &mut dyn Sampler) -> (Vec3, Option<HitRecord>);
+
+/// 内置场景的组成部分：摄影机、物体、光源、背景与着色函数
+///
+/// 光源同时也应出现在物体中，只有对光源直接采样的着色函数才会用到光源列表
+pub type SceneParts = (
+    Arc<dyn Camera>,
+    HittableList,
+    HittableList,
+    Background,
+    RayColorFn,
+);
 
 #[derive(Clone, Copy, Debug)]
 pub enum Scene {
@@ -18,6 +40,9 @@ pub enum Scene {
     ShinyMetal,
     FuzzyMetal,
     HollowGlassSpheres,
+    CornellBox,
+    BouncingSpheres,
+    CornellSmoke,
 }
 
 impl FromStr for Scene {
@@ -30,16 +55,14 @@ impl FromStr for Scene {
             "shiny-metal" => Ok(Scene::ShinyMetal),
             "fuzzy-metal" => Ok(Scene::FuzzyMetal),
             "hollow-glass-spheres" => Ok(Scene::HollowGlassSpheres),
+            "cornell-box" => Ok(Scene::CornellBox),
+            "bouncing-spheres" => Ok(Scene::BouncingSpheres),
+            "cornell-smoke" => Ok(Scene::CornellSmoke),
             _ => Err(format!("Unknown scene {}", s)),
         }
     }
 }
 
-fn background_color(y: Float) -> Vec3 {
-    let t = 0.5 * (y + 1.0);
-    Vec3::new(0.5, 0.7, 1.0) * t + (1. - t)
-}
-
 fn two_sphere(ground: Arc<dyn Material>, center: Arc<dyn Material>) -> HittableList {
     let hittables: Vec<Arc<dyn Hittable>> = vec![
         Arc::new(Sphere::new_xyzr(0.0, 0.0, -1.0, 0.5, center)),
@@ -49,7 +72,7 @@ fn two_sphere(ground: Arc<dyn Material>, center: Arc<dyn Material>) -> HittableL
 }
 
 /// 插图 10: 渲染带有半球散射的漫反射球体
-pub fn diffuse_spheres(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList, RayColorFn) {
+pub fn diffuse_spheres(aspect_ratio: Float) -> SceneParts {
     // 场景
     let dummy_material = Arc::new(DummyMaterial {});
     let world = two_sphere(dummy_material.clone(), dummy_material);
@@ -58,24 +81,17 @@ pub fn diffuse_spheres(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList, R
     let viewport_height = 2.0;
     let camera = Arc::new(SimpleCamera::new(viewport_height, aspect_ratio));
 
-    // 着色函数
-    fn diffuse_spheres_ray_color(ray: &Ray, world: &dyn Hittable, depth: i32) -> Vec3 {
-        if depth <= 0 {
-            return Vec3::zeros();
-        }
-        if let Some(rec) = world.hit(ray, 0.001, floats::MAX) {
-            let target = rec.p + rec.normal + Vec3::random_unit_vector();
-            diffuse_spheres_ray_color(&Ray::new(rec.p, target - rec.p), world, depth - 1) * 0.5
-        } else {
-            background_color(ray.direction.unit().y)
-        }
-    }
-
-    (camera, world, diffuse_spheres_ray_color)
+    (
+        camera,
+        world,
+        HittableList::new(),
+        Background::Sky,
+        diffuse_ray_color,
+    )
 }
 
 /// 插图 11: 闪亮金属
-pub fn shiny_metal(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList, RayColorFn) {
+pub fn shiny_metal(aspect_ratio: Float) -> SceneParts {
     let material_ground = Arc::new(Lambertian::new(0.8, 0.8, 0.0));
     let material_center = Arc::new(Lambertian::new(0.7, 0.3, 0.3));
     let material_left = Arc::new(Metal::new(0.8, 0.8, 0.8, 0.0));
@@ -92,11 +108,17 @@ pub fn shiny_metal(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList, RayCo
     // 摄影机
     let viewport_height = 2.0;
     let camera = Arc::new(SimpleCamera::new(viewport_height, aspect_ratio));
-    (camera, world, ray_color)
+    (
+        camera,
+        world,
+        HittableList::new(),
+        Background::Sky,
+        ray_color,
+    )
 }
 
 /// 插图 12: 模糊金属
-pub fn fuzzy_metal(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList, RayColorFn) {
+pub fn fuzzy_metal(aspect_ratio: Float) -> SceneParts {
     let material_ground = Arc::new(Lambertian::new(0.8, 0.8, 0.0));
     let material_center = Arc::new(Lambertian::new(0.7, 0.3, 0.3));
     let material_left = Arc::new(Metal::new(0.8, 0.8, 0.8, 0.3));
@@ -113,11 +135,17 @@ pub fn fuzzy_metal(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList, RayCo
     // 摄影机
     let viewport_height = 2.0;
     let camera = Arc::new(SimpleCamera::new(viewport_height, aspect_ratio));
-    (camera, world, ray_color)
+    (
+        camera,
+        world,
+        HittableList::new(),
+        Background::Sky,
+        ray_color,
+    )
 }
 
 /// 插图 16: 中空玻璃球
-pub fn hollow_glass_sphere(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList, RayColorFn) {
+pub fn hollow_glass_sphere(aspect_ratio: Float) -> SceneParts {
     // 场景
     let material_ground = Arc::new(Lambertian::new(0.8, 0.8, 0.0));
     let material_center = Arc::new(Lambertian::new(0.1, 0.2, 0.5));
@@ -141,32 +169,54 @@ pub fn hollow_glass_sphere(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableLis
     // 摄影机
     let viewport_height = 2.0;
     let camera = Arc::new(SimpleCamera::new(viewport_height, aspect_ratio));
-    (camera, world, ray_color)
+    (
+        camera,
+        world,
+        HittableList::new(),
+        Background::Sky,
+        ray_color,
+    )
 }
 
-// 最终场景
-pub fn random_final_scene(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList, RayColorFn) {
+// 最终场景，小球的位置与材质由 `seed` 决定
+pub fn random_final_scene(aspect_ratio: Float, seed: u64) -> SceneParts {
+    weekend_scene(aspect_ratio, false, &mut RandomSampler::new(seed))
+}
+
+/// 最终场景的运动模糊版本：漫反射小球在快门期间向上弹起
+pub fn bouncing_spheres(aspect_ratio: Float, seed: u64) -> SceneParts {
+    weekend_scene(aspect_ratio, true, &mut RandomSampler::new(seed))
+}
+
+fn weekend_scene(aspect_ratio: Float, bouncing: bool, sampler: &mut dyn Sampler) -> SceneParts {
     let pivot = Vec3::new(4., 0.2, 0.);
     let mut hittables = (-11..11)
-        .into_iter()
-        .map(|a| {
+        .flat_map(|a| {
             (-11..11)
-                .into_iter()
-                .map(|b| -> Option<Arc<dyn Hittable>> {
-                    let material_score = random();
-                    let x = random();
-                    let z = random();
+                .filter_map(|b| -> Option<Arc<dyn Hittable>> {
+                    let material_score = sampler.random();
+                    let x = sampler.random();
+                    let z = sampler.random();
                     let center = Vec3::new(a as Float + 0.9 * x, 0.2, b as Float + 0.9 * z);
                     if (center - pivot).length() > 0.9 {
                         let material: Arc<dyn Material> = if material_score < 0.8 {
-                            Arc::new(Lambertian {
-                                albedo: Vec3::random().powi(2),
-                            })
+                            let material = Arc::new(Lambertian::textured(Arc::new(
+                                SolidColor::from(Vec3::random(sampler).powi(2)),
+                            )));
+                            if bouncing {
+                                let center1 =
+                                    center + Vec3::new(0., sampler.random_between(0., 0.5), 0.);
+                                return Some(Arc::new(MovingSphere::new(
+                                    center, center1, 0., 1., 0.2, material,
+                                )));
+                            }
+                            material
                         } else if material_score < 0.95 {
-                            Arc::new(Metal {
-                                albedo: Vec3::random_between(0.5, 1.),
-                                fuzz: 0.5 * random(),
-                            })
+                            let albedo = Vec3::random_between(sampler, 0.5, 1.);
+                            Arc::new(Metal::textured(
+                                Arc::new(SolidColor::from(albedo)),
+                                0.5 * sampler.random(),
+                            ))
                         } else {
                             Arc::new(Dielectric::new(1.5))
                         };
@@ -175,10 +225,8 @@ pub fn random_final_scene(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList
                         None
                     }
                 })
-                .flatten()
                 .collect::<Vec<Arc<dyn Hittable>>>()
         })
-        .flatten()
         .collect::<Vec<Arc<dyn Hittable>>>();
     // 创建中心球体
     hittables.push(Arc::new(Sphere::new_xyzr(
@@ -200,10 +248,7 @@ pub fn random_final_scene(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList
         1.,
         0.,
         1.,
-        Arc::new(Metal {
-            albedo: Vec3::new(0.7, 0.6, 0.5),
-            fuzz: 0.1,
-        }),
+        Arc::new(Metal::new(0.7, 0.6, 0.5, 0.1)),
     )));
     // 创建地面
     let ground_material = Arc::new(Lambertian::new(0.5, 0.5, 0.5));
@@ -222,7 +267,7 @@ pub fn random_final_scene(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList
     let vup = Vec3::new(0., 1., 0.);
     let focal_dist = 10.;
     let aperture = 0.1;
-    let camera = Arc::new(PositionalCamera::new(
+    let camera = PositionalCamera::new(
         lookfrom,
         lookat,
         vup,
@@ -230,37 +275,317 @@ pub fn random_final_scene(aspect_ratio: Float) -> (Arc<dyn Camera>, HittableList
         aspect_ratio,
         aperture,
         focal_dist,
-    ));
+    );
+    let camera = if bouncing {
+        camera.with_shutter(0., 1.)
+    } else {
+        camera
+    };
+
+    (
+        Arc::new(camera),
+        world,
+        HittableList::new(),
+        Background::Sky,
+        ray_color,
+    )
+}
+
+/// 插图 10 的着色函数：忽略材质，以固定的反照率 0.5 在半球内散射
+pub fn diffuse_ray_color(
+    ray: &Ray,
+    world: &World,
+    depth: i32,
+    sampler: &mut dyn Sampler,
+) -> (Vec3, Option<HitRecord>) {
+    if depth <= 0 {
+        return (Vec3::zeros(), None);
+    }
+    let ray = &ray.with_medium_sample(sampler.random());
+    if let Some(rec) = world.objects.hit(ray, 0.001, Float::MAX) {
+        let target = rec.p + rec.normal + Vec3::random_unit_vector(sampler);
+        let ray = rec.spawn_ray(target - rec.p, ray.t);
+        let (incoming, _) = diffuse_ray_color(&ray, world, depth - 1, sampler);
+        (incoming * 0.5, Some(rec))
+    } else {
+        (world.background.color(&ray.direction.unit()), None)
+    }
+}
+
+/// Cornell 盒：只由顶部的面光源照明，背景为黑色
+pub fn cornell_box(aspect_ratio: Float) -> SceneParts {
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(0.73, 0.73, 0.73));
+    let (mut hittables, light) = cornell_walls(&white);
+    // 两个长方体
+    hittables.push(Arc::new(TriangleMesh::cuboid(
+        Vec3::new(265., 0., 295.),
+        Vec3::new(430., 330., 460.),
+        white.clone(),
+    )));
+    hittables.push(Arc::new(TriangleMesh::cuboid(
+        Vec3::new(130., 0., 65.),
+        Vec3::new(295., 165., 230.),
+        white,
+    )));
+    (
+        cornell_camera(aspect_ratio),
+        HittableList::from(hittables),
+        HittableList::from(vec![light]),
+        Background::Solid(Vec3::zeros()),
+        mis_ray_color,
+    )
+}
+
+/// Cornell 盒中的两个长方体换成旋转放置的黑烟与白雾
+pub fn cornell_smoke(aspect_ratio: Float) -> SceneParts {
+    let white: Arc<dyn Material> = Arc::new(Lambertian::new(0.73, 0.73, 0.73));
+    let (mut hittables, light) = cornell_walls(&white);
+    let block = |size: Vec3, degrees: Float, offset: Vec3| -> Arc<dyn Hittable> {
+        let cuboid = Arc::new(TriangleMesh::cuboid(Vec3::zeros(), size, white.clone()));
+        Arc::new(Instance::new(
+            cuboid,
+            Mat4::translate(offset) * Mat4::rotate(Vec3::new(0., 1., 0.), degrees),
+        ))
+    };
+    hittables.push(Arc::new(ConstantMedium::new(
+        block(Vec3::new(165., 330., 165.), 15., Vec3::new(265., 0., 295.)),
+        0.01,
+        Arc::new(Isotropic::new(0., 0., 0.)),
+    )));
+    hittables.push(Arc::new(ConstantMedium::new(
+        block(Vec3::new(165., 165., 165.), -18., Vec3::new(130., 0., 65.)),
+        0.01,
+        Arc::new(Isotropic::new(1., 1., 1.)),
+    )));
+    (
+        cornell_camera(aspect_ratio),
+        HittableList::from(hittables),
+        HittableList::from(vec![light]),
+        Background::Solid(Vec3::zeros()),
+        mis_ray_color,
+    )
+}
+
+/// Cornell 盒的墙壁与顶部的面光源，光源同时包含在墙壁列表中
+fn cornell_walls(white: &Arc<dyn Material>) -> (Vec<Arc<dyn Hittable>>, Arc<dyn Hittable>) {
+    let red: Arc<dyn Material> = Arc::new(Lambertian::new(0.65, 0.05, 0.05));
+    let green: Arc<dyn Material> = Arc::new(Lambertian::new(0.12, 0.45, 0.15));
+    let light: Arc<dyn Material> = Arc::new(DiffuseLight::new(15., 15., 15.));
 
-    (camera, world, ray_color)
+    let quad = |q: (Float, Float, Float),
+                u: (Float, Float, Float),
+                v: (Float, Float, Float),
+                m: &Arc<dyn Material>|
+     -> Arc<dyn Hittable> {
+        Arc::new(TriangleMesh::quad(
+            Vec3::from(q),
+            Vec3::from(u),
+            Vec3::from(v),
+            m.clone(),
+        ))
+    };
+    // 天花板上的光源，法线朝下
+    let lamp = quad((343., 554., 332.), (-130., 0., 0.), (0., 0., -105.), &light);
+    let walls = vec![
+        // 左右两侧的墙壁
+        quad((555., 0., 0.), (0., 0., 555.), (0., 555., 0.), &green),
+        quad((0., 0., 0.), (0., 555., 0.), (0., 0., 555.), &red),
+        lamp.clone(),
+        // 地板、天花板与后墙
+        quad((0., 0., 0.), (0., 0., 555.), (555., 0., 0.), white),
+        quad((555., 555., 555.), (-555., 0., 0.), (0., 0., -555.), white),
+        quad((0., 0., 555.), (0., 555., 0.), (555., 0., 0.), white),
+    ];
+    (walls, lamp)
+}
+
+fn cornell_camera(aspect_ratio: Float) -> Arc<dyn Camera> {
+    Arc::new(PositionalCamera::new(
+        Vec3::new(278., 278., -800.),
+        Vec3::new(278., 278., 0.),
+        Vec3::new(0., 1., 0.),
+        40.,
+        aspect_ratio,
+        0.,
+        10.,
+    ))
 }
 
 // 最终场景的着色函数
-pub fn ray_color(ray: &Ray, world: &dyn Hittable, depth: i32) -> Vec3 {
+pub fn ray_color(
+    ray: &Ray,
+    world: &World,
+    depth: i32,
+    sampler: &mut dyn Sampler,
+) -> (Vec3, Option<HitRecord>) {
     if depth <= 0 {
-        Vec3::zeros()
+        return (Vec3::zeros(), None);
+    }
+    let ray = &ray.with_medium_sample(sampler.random());
+    if let Some(rec) = world.objects.hit(ray, 0.001, Float::MAX) {
+        let mut color = rec.material.emitted(&rec);
+        if let Some(scattered) = rec.material.scatter(ray, &rec, sampler) {
+            let (incoming, _) = ray_color(&scattered.ray, world, depth - 1, sampler);
+            color = color + scattered.attenuation * incoming;
+        }
+        (color, Some(rec))
     } else {
-        if let Some(rec) = world.hit(ray, 0.001, floats::MAX) {
-            if let Some(scattered) = rec.material.scatter(ray, &rec) {
-                scattered.attenuation * ray_color(&scattered.ray, world, depth - 1)
-            } else {
-                Vec3::zeros()
+        (world.background.color(&ray.direction), None)
+    }
+}
+
+/// 对光源直接采样（next-event estimation）并与按材质采样以多重重要性采样结合的着色函数
+///
+/// 两种策略均在方向空间上采样，对同一方向取首个交点的自发光，以幂启发式分配权重，
+/// 其期望与 `ray_color` 相同。delta 分布的材质只能按材质采样。
+pub fn mis_ray_color(
+    ray: &Ray,
+    world: &World,
+    depth: i32,
+    sampler: &mut dyn Sampler,
+) -> (Vec3, Option<HitRecord>) {
+    mis_trace(ray, world, depth, None, sampler)
+}
+
+/// `bsdf_pdf` 为按材质采样得到 `ray` 的概率密度，摄影机光线与 delta 分布的散射光线为 `None`
+fn mis_trace(
+    ray: &Ray,
+    world: &World,
+    depth: i32,
+    bsdf_pdf: Option<Float>,
+    sampler: &mut dyn Sampler,
+) -> (Vec3, Option<HitRecord>) {
+    if depth <= 0 {
+        return (Vec3::zeros(), None);
+    }
+    // 每次弹射抽取一个随机数供参与介质使用，采样器的维度不受场景几何影响
+    let ray = &ray.with_medium_sample(sampler.random());
+    let rec = match world.objects.hit(ray, 0.001, Float::MAX) {
+        Some(rec) => rec,
+        None => return (world.background.color(&ray.direction), None),
+    };
+
+    let mut color = rec.material.emitted(&rec);
+    if let Some(bsdf_pdf) = bsdf_pdf {
+        // 上一个交点的光源采样同样可能得到这一方向
+        color = color * power_heuristic(bsdf_pdf, world.lights.pdf_value(ray));
+    }
+
+    let scattered = match rec.material.scatter(ray, &rec, sampler) {
+        Some(scattered) => scattered,
+        None => return (color, Some(rec)),
+    };
+    if scattered.pdf.is_some() && !world.lights.objects().is_empty() {
+        // 归一化使 t_min 与散射光线一样对应相近的距离
+        let direction = world.lights.random_direction(&rec.p, ray.t, sampler).unit();
+        let light_ray = rec
+            .spawn_ray(direction, ray.t)
+            .with_medium_sample(sampler.random());
+        let light_pdf = world.lights.pdf_value(&light_ray);
+        let f = rec.material.eval(ray, &rec, &direction);
+        if light_pdf > 0. && f != Vec3::zeros() {
+            if let Some(light_rec) = world.objects.hit(&light_ray, 0.001, Float::MAX) {
+                let weight = power_heuristic(light_pdf, rec.material.pdf(ray, &rec, &direction));
+                color = color + f * light_rec.material.emitted(&light_rec) * (weight / light_pdf);
             }
-        } else {
-            background_color(ray.direction.y)
         }
     }
+    let (incoming, _) = mis_trace(&scattered.ray, world, depth - 1, scattered.pdf, sampler);
+    (color + scattered.attenuation * incoming, Some(rec))
 }
 
+/// 幂指数为 2 的幂启发式权重
+fn power_heuristic(pdf: Float, other: Float) -> Float {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b == 0. {
+        0.
+    } else {
+        a / (a + b)
+    }
+}
+
+/// 创建场景，`use_bvh` 为真时以层次包围盒代替线性扫描求交，`seed` 决定随机生成的场景内容
 pub fn create_scene(
     scene: Scene,
     aspect_ratio: Float,
-) -> (Arc<dyn Camera>, HittableList, RayColorFn) {
-    match scene {
+    use_bvh: bool,
+    seed: u64,
+) -> (Arc<dyn Camera>, World, RayColorFn) {
+    let (camera, objects, lights, background, ray_color) = match scene {
         Scene::DiffuseSpheres => diffuse_spheres(aspect_ratio),
         Scene::ShinyMetal => shiny_metal(aspect_ratio),
         Scene::FuzzyMetal => fuzzy_metal(aspect_ratio),
         Scene::HollowGlassSpheres => hollow_glass_sphere(aspect_ratio),
-        Scene::Weekend => random_final_scene(aspect_ratio),
+        Scene::Weekend => random_final_scene(aspect_ratio, seed),
+        Scene::CornellBox => cornell_box(aspect_ratio),
+        Scene::BouncingSpheres => bouncing_spheres(aspect_ratio, seed),
+        Scene::CornellSmoke => cornell_smoke(aspect_ratio),
+    };
+    (
+        camera,
+        build_world(objects, lights, background, use_bvh),
+        ray_color,
+    )
+}
+
+/// 将物体列表、光源与背景组装为场景，`use_bvh` 为真时以层次包围盒组织物体
+pub fn build_world(
+    objects: HittableList,
+    lights: HittableList,
+    background: Background,
+    use_bvh: bool,
+) -> World {
+    let objects: Arc<dyn Hittable> = if use_bvh {
+        Arc::new(Bvh::from(objects))
+    } else {
+        Arc::new(objects)
+    };
+    World {
+        objects,
+        lights,
+        background,
     }
 }
+
+#[test]
+fn test_mis_converges_to_ray_color() {
+    // 两种着色函数在 Cornell 盒中的平均亮度应当一致，而 MIS 的方差更小
+    let (camera, objects, lights, background, _) = cornell_box(1.);
+    let world = build_world(objects, lights, background, true);
+    let size = 8;
+    // 返回整幅图像的平均亮度与像素内单个样本亮度方差的平均
+    let render = |ray_color: RayColorFn, samples: usize| {
+        let mut sampler = RandomSampler::new(5);
+        let (mut mean, mut variance) = (0., 0.);
+        for y in 0..size {
+            for x in 0..size {
+                let (mut sum, mut sum_squared) = (0., 0.);
+                for _ in 0..samples {
+                    let u = (x as Float + sampler.random()) / size as Float;
+                    let v = (y as Float + sampler.random()) / size as Float;
+                    let ray = camera.ray(u, v, &mut sampler);
+                    let luminance = ray_color(&ray, &world, 50, &mut sampler).0.sum() as f64;
+                    sum += luminance;
+                    sum_squared += luminance * luminance;
+                }
+                let pixel = sum / samples as f64;
+                mean += pixel;
+                variance += sum_squared / samples as f64 - pixel * pixel;
+            }
+        }
+        let pixels = (size * size) as f64;
+        (mean / pixels, variance / pixels)
+    };
+
+    let (expected, expected_variance) = render(ray_color, 2048);
+    let (actual, actual_variance) = render(mis_ray_color, 1024);
+    // 按材质采样的估计在此样本数下的标准误差约为 1.5%
+    assert!(
+        ((actual - expected) / expected).abs() < 0.05,
+        "{} vs {}",
+        actual,
+        expected
+    );
+    // 光源较大，间接光照的方差占多数，MIS 约将方差降低三分之一
+    assert!(actual_variance < expected_variance * 0.8);
+}
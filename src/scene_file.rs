@@ -0,0 +1,996 @@
+//! TOML 格式的场景描述文件
+//!
+//! ```toml
+//! integrator = "material"
+//! background = "sky"
+//!
+//! [camera]
+//! type = "positional"
+//! lookfrom = [13.0, 2.0, 3.0]
+//! lookat = [0.0, 0.0, 0.0]
+//! vup = [0.0, 1.0, 0.0]
+//! vfov = 20.0
+//! aperture = 0.1
+//! focal_dist = 10.0
+//!
+//! [materials.ground]
+//! type = "lambertian"
+//! albedo = [0.5, 0.5, 0.5]
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0.0, -1000.0, 0.0]
+//! radius = 1000.0
+//! material = "ground"
+//!
+//! [[objects]]
+//! type = "sphere"
+//! center = [0.0, 1.0, 0.0]
+//! radius = 1.0
+//! material = { type = "dielectric", ior = 1.5 }
+//!
+//! # 具名形状只构建一次，可被任意多个实例引用
+//! [shapes.teapot]
+//! type = "obj"
+//! path = "teapot.obj"
+//!
+//! [[objects]]
+//! type = "instance"
+//! object = "teapot"
+//! translate = [2.0, 0.0, 0.0]
+//! rotate = { axis = [0.0, 1.0, 0.0], degrees = 45.0 }
+//! scale = [0.5, 0.5, 0.5]
+//! ```
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use crate::camera::{Camera, PositionalCamera, SimpleCamera};
+use crate::conductor::{ComplexIor, ConductorPreset};
+use crate::hittable::{Hittable, HittableList};
+use crate::image::ImageError;
+use crate::instance::Instance;
+use crate::mat4::Mat4;
+use crate::material::{
+    AnyMaterial, Dielectric, DiffuseLight, DummyMaterial, Isotropic, Lambertian, Metal, Principled,
+    RoughConductor, RoughDielectric,
+};
+use crate::math::Float;
+use crate::medium::ConstantMedium;
+use crate::microfacet::{Distribution, Microfacet};
+use crate::obj::{load_obj_recording, ObjError};
+use crate::sampler::RandomSampler;
+use crate::scene::{build_world, diffuse_ray_color, mis_ray_color, ray_color, RayColorFn};
+use crate::sphere::{MovingSphere, Sphere};
+use crate::texture::{AnyTexture, CheckerTexture, ImageTexture, MarbleTexture, SolidColor};
+use crate::triangle::{Triangle, TriangleMesh};
+use crate::vec3::Vec3;
+use crate::world::{Background, World};
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    IOError(std::io::Error),
+    TOMLError(toml::de::Error),
+    ObjError(ObjError),
+    ImageError(ImageError),
+    UnknownMaterial(String),
+    UnknownShape(String),
+    RecursiveShape(String),
+    SingularTransform,
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneFileError::IOError(e) => write!(f, "{}", e),
+            SceneFileError::TOMLError(e) => write!(f, "{}", e),
+            SceneFileError::ObjError(e) => write!(f, "{}", e),
+            SceneFileError::ImageError(e) => write!(f, "{:?}", e),
+            SceneFileError::UnknownMaterial(name) => write!(f, "unknown material `{}`", name),
+            SceneFileError::UnknownShape(name) => write!(f, "unknown shape `{}`", name),
+            SceneFileError::RecursiveShape(name) => write!(f, "shape `{}` refers to itself", name),
+            SceneFileError::SingularTransform => write!(f, "instance transform is not invertible"),
+        }
+    }
+}
+
+type Vector = [Float; 3];
+
+fn vec3(v: &Vector) -> Vec3 {
+    Vec3::new(v[0], v[1], v[2])
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SceneFile {
+    pub camera: CameraDesc,
+    #[serde(default)]
+    pub integrator: IntegratorDesc,
+    #[serde(default)]
+    pub background: BackgroundDesc,
+    #[serde(default)]
+    pub materials: BTreeMap<String, MaterialDesc>,
+    /// 具名形状，只在被 `instance` 引用时构建，且无论被引用多少次只构建一次
+    #[serde(default)]
+    pub shapes: BTreeMap<String, ObjectDesc>,
+    #[serde(default)]
+    pub objects: Vec<ObjectDesc>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum CameraDesc {
+    Simple {
+        viewport_height: Float,
+    },
+    Positional {
+        lookfrom: Vector,
+        lookat: Vector,
+        vup: Vector,
+        vfov: Float,
+        #[serde(default)]
+        aperture: Float,
+        focal_dist: Float,
+        /// 快门开启与关闭的时刻，缺省时不产生运动模糊
+        #[serde(default)]
+        shutter: [Float; 2],
+    },
+}
+
+/// 着色函数
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IntegratorDesc {
+    /// 按材质散射，见 `scene::ray_color`
+    #[default]
+    Material,
+    /// 忽略材质的漫反射，见 `scene::diffuse_ray_color`
+    Diffuse,
+    /// 对光源直接采样并以多重重要性采样结合，见 `scene::mis_ray_color`
+    Mis,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BackgroundDesc {
+    #[default]
+    Sky,
+    #[serde(untagged)]
+    Solid(Vector),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum MaterialDesc {
+    Lambertian {
+        albedo: TextureDesc,
+    },
+    Metal {
+        albedo: TextureDesc,
+        fuzz: Float,
+    },
+    Dielectric {
+        ior: Float,
+        /// 内部介质的吸收系数，缺省为零即无色透明
+        #[serde(default)]
+        absorption: Vector,
+    },
+    /// 微表面导体，`alpha` 为法线分布的粗糙度参数
+    RoughConductor {
+        albedo: TextureDesc,
+        alpha: Float,
+        #[serde(default)]
+        distribution: Distribution,
+    },
+    /// 以复折射率描述的金属，`alpha` 缺省为零即光滑表面
+    Conductor {
+        ior: ComplexIorDesc,
+        #[serde(default)]
+        alpha: Float,
+        #[serde(default)]
+        distribution: Distribution,
+    },
+    /// 微表面电介质，例如磨砂玻璃
+    RoughDielectric {
+        ior: Float,
+        alpha: Float,
+        #[serde(default)]
+        distribution: Distribution,
+    },
+    /// 迪士尼原理化材质，未给出的参数取默认值
+    Principled(PrincipledDesc),
+    DiffuseLight {
+        emit: Vector,
+    },
+    /// 参与介质的各向同性相函数
+    Isotropic {
+        albedo: TextureDesc,
+    },
+    Dummy,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PrincipledDesc {
+    pub base_color: TextureDesc,
+    pub metallic: Float,
+    pub roughness: Float,
+    pub specular: Float,
+    pub specular_tint: Float,
+    pub sheen: Float,
+    pub sheen_tint: Float,
+    pub clearcoat: Float,
+    pub clearcoat_gloss: Float,
+    pub transmission: Float,
+    pub ior: Float,
+    pub anisotropic: Float,
+}
+
+impl Default for PrincipledDesc {
+    fn default() -> Self {
+        PrincipledDesc {
+            base_color: white_texture(),
+            metallic: 0.,
+            roughness: 0.5,
+            specular: 0.5,
+            specular_tint: 0.,
+            sheen: 0.,
+            sheen_tint: 0.5,
+            clearcoat: 0.,
+            clearcoat_gloss: 1.,
+            transmission: 0.,
+            ior: 1.5,
+            anisotropic: 0.,
+        }
+    }
+}
+
+impl PrincipledDesc {
+    fn build(&self, assets: &Assets) -> Result<Principled, SceneFileError> {
+        Ok(Principled::textured(self.base_color.build(assets)?)
+            .with_metallic(self.metallic)
+            .with_roughness(self.roughness)
+            .with_specular(self.specular, self.specular_tint)
+            .with_sheen(self.sheen, self.sheen_tint)
+            .with_clearcoat(self.clearcoat, self.clearcoat_gloss)
+            .with_transmission(self.transmission, self.ior)
+            .with_anisotropic(self.anisotropic))
+    }
+}
+
+/// 复折射率，可以写作预设的金属名称，例如 `ior = "gold"`
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ComplexIorDesc {
+    Preset(ConductorPreset),
+    Custom { eta: Vector, k: Vector },
+}
+
+impl ComplexIorDesc {
+    fn build(&self) -> ComplexIor {
+        match self {
+            ComplexIorDesc::Preset(preset) => preset.ior(),
+            ComplexIorDesc::Custom { eta, k } => ComplexIor::new(vec3(eta), vec3(k)),
+        }
+    }
+}
+
+/// 纹理，可以直接写作 RGB 颜色
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TextureDesc {
+    Color(Vector),
+    Pattern(PatternDesc),
+}
+
+fn white() -> Vector {
+    [1., 1., 1.]
+}
+
+fn white_texture() -> TextureDesc {
+    TextureDesc::Color(white())
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum PatternDesc {
+    Checker {
+        scale: Float,
+        even: Box<TextureDesc>,
+        odd: Box<TextureDesc>,
+    },
+    Marble {
+        scale: Float,
+        #[serde(default = "white")]
+        color: Vector,
+        /// 生成噪声所用的随机数种子
+        #[serde(default)]
+        seed: u64,
+    },
+    /// PNG 图像，相对路径相对于场景文件所在目录
+    Image { path: String },
+}
+
+impl TextureDesc {
+    fn build(&self, assets: &Assets) -> Result<AnyTexture, SceneFileError> {
+        Ok(match self {
+            TextureDesc::Color(color) => Arc::new(SolidColor::from(vec3(color))),
+            TextureDesc::Pattern(PatternDesc::Checker { scale, even, odd }) => Arc::new(
+                CheckerTexture::new(*scale, even.build(assets)?, odd.build(assets)?),
+            ),
+            TextureDesc::Pattern(PatternDesc::Marble { scale, color, seed }) => Arc::new(
+                MarbleTexture::new(*scale, vec3(color), &mut RandomSampler::new(*seed)),
+            ),
+            TextureDesc::Pattern(PatternDesc::Image { path }) => {
+                Arc::new(ImageTexture::load(assets.path(path)).map_err(SceneFileError::ImageError)?)
+            }
+        })
+    }
+}
+
+/// 对材质的引用，可以是 `materials` 中的名称，也可以直接内联定义
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum MaterialRef {
+    Named(String),
+    Inline(MaterialDesc),
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case", deny_unknown_fields)]
+pub enum ObjectDesc {
+    Sphere {
+        center: Vector,
+        radius: Float,
+        material: MaterialRef,
+    },
+    /// 在 `time0` 至 `time1` 间自 `center0` 匀速移动到 `center1` 的球体
+    MovingSphere {
+        center0: Vector,
+        center1: Vector,
+        #[serde(default)]
+        time0: Float,
+        #[serde(default = "default_time1")]
+        time1: Float,
+        radius: Float,
+        material: MaterialRef,
+    },
+    Triangle {
+        vertices: [Vector; 3],
+        material: MaterialRef,
+    },
+    /// 以 `q` 为一角、`u` 与 `v` 为两边的平行四边形
+    Quad {
+        q: Vector,
+        u: Vector,
+        v: Vector,
+        material: MaterialRef,
+    },
+    /// 以 `min`、`max` 为对角顶点的轴对齐长方体
+    Cuboid {
+        min: Vector,
+        max: Vector,
+        material: MaterialRef,
+    },
+    Mesh {
+        positions: Vec<Vector>,
+        indices: Vec<[usize; 3]>,
+        material: MaterialRef,
+    },
+    /// Wavefront OBJ 文件，相对路径相对于场景文件所在目录
+    Obj { path: String },
+    /// 充满 `boundary` 所围区域的均匀介质，如雾与烟
+    Medium {
+        boundary: ShapeRef,
+        density: Float,
+        #[serde(default = "white_texture")]
+        albedo: TextureDesc,
+    },
+    /// 依次经缩放、旋转、平移放置的形状
+    Instance {
+        object: ShapeRef,
+        #[serde(default)]
+        translate: Vector,
+        #[serde(default)]
+        rotate: Option<RotateDesc>,
+        #[serde(default = "default_scale")]
+        scale: Vector,
+    },
+}
+
+/// 具名形状的名称或形状的完整定义
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum ShapeRef {
+    Named(String),
+    Inline(Box<ObjectDesc>),
+}
+
+/// 绕过原点的 `axis` 轴逆时针旋转 `degrees` 度
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RotateDesc {
+    pub axis: Vector,
+    pub degrees: Float,
+}
+
+fn default_scale() -> Vector {
+    [1., 1., 1.]
+}
+
+fn default_time1() -> Float {
+    1.
+}
+
+impl MaterialDesc {
+    fn build(&self, assets: &Assets) -> Result<AnyMaterial, SceneFileError> {
+        Ok(match self {
+            MaterialDesc::Lambertian { albedo } => {
+                Arc::new(Lambertian::textured(albedo.build(assets)?))
+            }
+            MaterialDesc::Metal { albedo, fuzz } => {
+                Arc::new(Metal::textured(albedo.build(assets)?, *fuzz))
+            }
+            MaterialDesc::Dielectric { ior, absorption } => {
+                Arc::new(Dielectric::absorbing(*ior, vec3(absorption)))
+            }
+            MaterialDesc::RoughConductor {
+                albedo,
+                alpha,
+                distribution,
+            } => Arc::new(RoughConductor::textured(
+                albedo.build(assets)?,
+                Microfacet::new(*distribution, *alpha),
+            )),
+            MaterialDesc::Conductor {
+                ior,
+                alpha,
+                distribution,
+            } => Arc::new(RoughConductor::complex(
+                ior.build(),
+                Microfacet::new(*distribution, *alpha),
+            )),
+            MaterialDesc::RoughDielectric {
+                ior,
+                alpha,
+                distribution,
+            } => Arc::new(RoughDielectric::new(
+                *ior,
+                Microfacet::new(*distribution, *alpha),
+            )),
+            MaterialDesc::Principled(desc) => Arc::new(desc.build(assets)?),
+            MaterialDesc::DiffuseLight { emit } => {
+                Arc::new(DiffuseLight::new(emit[0], emit[1], emit[2]))
+            }
+            MaterialDesc::Isotropic { albedo } => {
+                Arc::new(Isotropic::textured(albedo.build(assets)?))
+            }
+            MaterialDesc::Dummy => Arc::new(DummyMaterial {}),
+        })
+    }
+}
+
+/// 外部文件的相对路径相对于场景文件所在目录解析，构建过程中读取的文件依次记录下来
+struct Assets<'a> {
+    base_dir: &'a Path,
+    files: RefCell<Vec<PathBuf>>,
+}
+
+impl Assets<'_> {
+    fn path(&self, relative: &str) -> PathBuf {
+        let path = self.base_dir.join(relative);
+        self.files.borrow_mut().push(path.clone());
+        path
+    }
+}
+
+/// 构建物体时共享的材质与具名形状
+struct BuildContext<'a> {
+    assets: &'a Assets<'a>,
+    materials: HashMap<&'a str, AnyMaterial>,
+    shapes: &'a BTreeMap<String, ObjectDesc>,
+    /// 已构建的具名形状，被多个实例引用时只构建一次
+    built_shapes: RefCell<HashMap<&'a str, Arc<dyn Hittable>>>,
+    /// 正在构建的具名形状，用于发现循环引用
+    building: RefCell<Vec<&'a str>>,
+}
+
+impl<'a> BuildContext<'a> {
+    fn material(&self, material: &MaterialRef) -> Result<AnyMaterial, SceneFileError> {
+        match material {
+            MaterialRef::Named(name) => self
+                .materials
+                .get(name.as_str())
+                .cloned()
+                .ok_or_else(|| SceneFileError::UnknownMaterial(name.clone())),
+            MaterialRef::Inline(desc) => desc.build(self.assets),
+        }
+    }
+
+    fn shape(&self, shape: &'a ShapeRef) -> Result<Arc<dyn Hittable>, SceneFileError> {
+        match shape {
+            ShapeRef::Named(name) => {
+                if let Some(built) = self.built_shapes.borrow().get(name.as_str()) {
+                    return Ok(built.clone());
+                }
+                let desc = self
+                    .shapes
+                    .get(name)
+                    .ok_or_else(|| SceneFileError::UnknownShape(name.clone()))?;
+                if self.building.borrow().contains(&name.as_str()) {
+                    return Err(SceneFileError::RecursiveShape(name.clone()));
+                }
+                self.building.borrow_mut().push(name.as_str());
+                let built = desc.build(self);
+                self.building.borrow_mut().pop();
+                let built = built?;
+                self.built_shapes
+                    .borrow_mut()
+                    .insert(name.as_str(), built.clone());
+                Ok(built)
+            }
+            ShapeRef::Inline(desc) => desc.build(self),
+        }
+    }
+}
+
+impl ObjectDesc {
+    fn material(&self) -> Option<&MaterialRef> {
+        match self {
+            ObjectDesc::Sphere { material, .. }
+            | ObjectDesc::MovingSphere { material, .. }
+            | ObjectDesc::Triangle { material, .. }
+            | ObjectDesc::Quad { material, .. }
+            | ObjectDesc::Cuboid { material, .. }
+            | ObjectDesc::Mesh { material, .. } => Some(material),
+            ObjectDesc::Obj { .. } | ObjectDesc::Medium { .. } | ObjectDesc::Instance { .. } => {
+                None
+            }
+        }
+    }
+
+    /// 是否使用漫射光源材质，须在形状的循环引用已被 `build` 排除后调用
+    fn is_light(&self, file: &SceneFile) -> bool {
+        if let ObjectDesc::Instance { object, .. } = self {
+            return match object {
+                ShapeRef::Named(name) => file.shapes.get(name).is_some_and(|s| s.is_light(file)),
+                ShapeRef::Inline(desc) => desc.is_light(file),
+            };
+        }
+        let desc = match self.material() {
+            Some(MaterialRef::Named(name)) => file.materials.get(name),
+            Some(MaterialRef::Inline(desc)) => Some(desc),
+            None => None,
+        };
+        matches!(desc, Some(MaterialDesc::DiffuseLight { .. }))
+    }
+
+    fn build<'a>(
+        &'a self,
+        context: &BuildContext<'a>,
+    ) -> Result<Arc<dyn Hittable>, SceneFileError> {
+        Ok(match self {
+            ObjectDesc::Sphere {
+                center,
+                radius,
+                material,
+            } => Arc::new(Sphere::new(
+                vec3(center),
+                *radius,
+                context.material(material)?,
+            )),
+            ObjectDesc::MovingSphere {
+                center0,
+                center1,
+                time0,
+                time1,
+                radius,
+                material,
+            } => Arc::new(MovingSphere::new(
+                vec3(center0),
+                vec3(center1),
+                *time0,
+                *time1,
+                *radius,
+                context.material(material)?,
+            )),
+            ObjectDesc::Triangle { vertices, material } => Arc::new(Triangle::new(
+                vec3(&vertices[0]),
+                vec3(&vertices[1]),
+                vec3(&vertices[2]),
+                context.material(material)?,
+            )),
+            ObjectDesc::Quad { q, u, v, material } => Arc::new(TriangleMesh::quad(
+                vec3(q),
+                vec3(u),
+                vec3(v),
+                context.material(material)?,
+            )),
+            ObjectDesc::Cuboid { min, max, material } => Arc::new(TriangleMesh::cuboid(
+                vec3(min),
+                vec3(max),
+                context.material(material)?,
+            )),
+            ObjectDesc::Mesh {
+                positions,
+                indices,
+                material,
+            } => Arc::new(TriangleMesh::with_material(
+                positions.iter().map(vec3).collect(),
+                indices.clone(),
+                context.material(material)?,
+            )),
+            ObjectDesc::Obj { path } => {
+                let (mesh, files) = load_obj_recording(context.assets.base_dir.join(path))
+                    .map_err(SceneFileError::ObjError)?;
+                context.assets.files.borrow_mut().extend(files);
+                Arc::new(mesh)
+            }
+            ObjectDesc::Medium {
+                boundary,
+                density,
+                albedo,
+            } => Arc::new(ConstantMedium::new(
+                context.shape(boundary)?,
+                *density,
+                Arc::new(Isotropic::textured(albedo.build(context.assets)?)),
+            )),
+            ObjectDesc::Instance {
+                object,
+                translate,
+                rotate,
+                scale,
+            } => {
+                let mut transform = Mat4::translate(vec3(translate));
+                if let Some(rotate) = rotate {
+                    transform = transform * Mat4::rotate(vec3(&rotate.axis), rotate.degrees);
+                }
+                transform = transform * Mat4::scale(vec3(scale));
+                if transform.inverse().is_none() {
+                    return Err(SceneFileError::SingularTransform);
+                }
+                Arc::new(Instance::new(context.shape(object)?, transform))
+            }
+        })
+    }
+}
+
+/// 构建好的摄影机、场景与积分器，以及构建时读取的全部外部文件
+pub type LoadedScene = (Arc<dyn Camera>, World, RayColorFn, Vec<PathBuf>);
+
+impl SceneFile {
+    pub fn parse(source: &str) -> Result<SceneFile, SceneFileError> {
+        toml::from_str(source).map_err(SceneFileError::TOMLError)
+    }
+
+    /// 构建场景，`base_dir` 为解析 OBJ 相对路径的目录
+    pub fn build(
+        &self,
+        aspect_ratio: Float,
+        use_bvh: bool,
+        base_dir: &Path,
+    ) -> Result<(Arc<dyn Camera>, World, RayColorFn), SceneFileError> {
+        let (camera, world, ray_color, _) =
+            self.build_recording(aspect_ratio, use_bvh, base_dir)?;
+        Ok((camera, world, ray_color))
+    }
+
+    /// 同 `build`，并返回构建时读取的全部外部文件，包括 OBJ 引用的 MTL 文件与贴图
+    pub fn build_recording(
+        &self,
+        aspect_ratio: Float,
+        use_bvh: bool,
+        base_dir: &Path,
+    ) -> Result<LoadedScene, SceneFileError> {
+        let camera: Arc<dyn Camera> = match &self.camera {
+            CameraDesc::Simple { viewport_height } => {
+                Arc::new(SimpleCamera::new(*viewport_height, aspect_ratio))
+            }
+            CameraDesc::Positional {
+                lookfrom,
+                lookat,
+                vup,
+                vfov,
+                aperture,
+                focal_dist,
+                shutter,
+            } => Arc::new(
+                PositionalCamera::new(
+                    vec3(lookfrom),
+                    vec3(lookat),
+                    vec3(vup),
+                    *vfov,
+                    aspect_ratio,
+                    *aperture,
+                    *focal_dist,
+                )
+                .with_shutter(shutter[0], shutter[1]),
+            ),
+        };
+
+        let assets = Assets {
+            base_dir,
+            files: RefCell::new(vec![]),
+        };
+        // 同名材质只创建一次，被多个物体共享；按名称顺序创建，使随机纹理可以复现
+        let materials = self
+            .materials
+            .iter()
+            .map(|(name, desc)| Ok((name.as_str(), desc.build(&assets)?)))
+            .collect::<Result<HashMap<&str, AnyMaterial>, SceneFileError>>()?;
+
+        let context = BuildContext {
+            assets: &assets,
+            materials,
+            shapes: &self.shapes,
+            built_shapes: RefCell::new(HashMap::new()),
+            building: RefCell::new(vec![]),
+        };
+        // 使用漫射光源材质的物体同时作为光源
+        let mut objects = HittableList::new();
+        let mut lights = HittableList::new();
+        for object in self.objects.iter() {
+            let hittable = object.build(&context)?;
+            if object.is_light(self) {
+                lights.push(hittable.clone());
+            }
+            objects.push(hittable);
+        }
+
+        let background = match self.background {
+            BackgroundDesc::Sky => Background::Sky,
+            BackgroundDesc::Solid(color) => Background::Solid(vec3(&color)),
+        };
+        let ray_color: RayColorFn = match self.integrator {
+            IntegratorDesc::Material => ray_color,
+            IntegratorDesc::Diffuse => diffuse_ray_color,
+            IntegratorDesc::Mis => mis_ray_color,
+        };
+        Ok((
+            camera,
+            build_world(objects, lights, background, use_bvh),
+            ray_color,
+            assets.files.into_inner(),
+        ))
+    }
+}
+
+/// 读取场景描述文件并构建场景，同时返回读取过的全部文件，第一项为场景描述文件本身
+pub fn load_scene_file<P: AsRef<Path>>(
+    path: P,
+    aspect_ratio: Float,
+    use_bvh: bool,
+) -> Result<LoadedScene, SceneFileError> {
+    let path = path.as_ref();
+    let source = std::fs::read_to_string(path).map_err(SceneFileError::IOError)?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new(""));
+    let (camera, world, ray_color, files) =
+        SceneFile::parse(&source)?.build_recording(aspect_ratio, use_bvh, base_dir)?;
+    let files = std::iter::once(path.to_path_buf()).chain(files).collect();
+    Ok((camera, world, ray_color, files))
+}
+
+#[test]
+fn test_example_scenes_match_builtin() {
+    use crate::sampler::Sampler;
+    use crate::scene::{create_scene, Scene};
+
+    fn render(camera: &dyn Camera, world: &World, ray_color: RayColorFn) -> Vec<Vec3> {
+        let (width, height) = (12, 8);
+        let mut pixels = vec![];
+        for y in 0..height {
+            for x in 0..width {
+                let mut sampler = RandomSampler::new((y * width + x) as u64);
+                let u = (x as Float + sampler.random()) / width as Float;
+                let v = 1. - (y as Float + sampler.random()) / height as Float;
+                let ray = camera.ray(u, v, &mut sampler);
+                pixels.push(ray_color(&ray, world, 50, &mut sampler).0);
+            }
+        }
+        pixels
+    }
+
+    let examples = [
+        (Scene::DiffuseSpheres, "diffuse-spheres"),
+        (Scene::ShinyMetal, "shiny-metal"),
+        (Scene::FuzzyMetal, "fuzzy-metal"),
+        (Scene::HollowGlassSpheres, "hollow-glass-spheres"),
+        (Scene::Weekend, "weekend"),
+        (Scene::CornellBox, "cornell-box"),
+        (Scene::CornellSmoke, "cornell-smoke"),
+    ];
+    for (scene, name) in examples {
+        let (camera, world, ray_color) = create_scene(scene, 1.5, true, 0);
+        let expected = render(camera.as_ref(), &world, ray_color);
+
+        let path = format!("{}/scenes/{}.toml", env!("CARGO_MANIFEST_DIR"), name);
+        let (camera, world, ray_color, _) = load_scene_file(&path, 1.5, true).unwrap();
+        let actual = render(camera.as_ref(), &world, ray_color);
+        assert_eq!(expected, actual, "{} differs from the built-in scene", name);
+    }
+}
+
+/// 在材质与物体的定义前补上简单摄影机，测试只需给出与之相关的部分
+#[cfg(test)]
+fn with_camera(body: &str) -> String {
+    format!(
+        "[camera]\ntype = \"simple\"\nviewport_height = 2.0\n{}",
+        body
+    )
+}
+
+#[cfg(test)]
+fn parse_with_camera(body: &str) -> Result<SceneFile, SceneFileError> {
+    SceneFile::parse(&with_camera(body))
+}
+
+/// 以统一的宽高比构建测试场景，不使用 BVH，外部文件相对于当前目录
+#[cfg(test)]
+fn build_test_scene(
+    scene: &SceneFile,
+) -> Result<(Arc<dyn Camera>, World, RayColorFn), SceneFileError> {
+    scene.build(1.5, false, Path::new(""))
+}
+
+#[test]
+fn test_unknown_material() {
+    let source = r#"
+        [[objects]]
+        type = "sphere"
+        center = [0.0, 0.0, -1.0]
+        radius = 0.5
+        material = "missing"
+    "#;
+    let scene = parse_with_camera(source).unwrap();
+    match build_test_scene(&scene) {
+        Err(SceneFileError::UnknownMaterial(name)) => assert_eq!(name, "missing"),
+        _ => panic!("expected an unknown material error"),
+    }
+}
+
+#[test]
+fn test_shape_instances() {
+    let source = r#"
+        [shapes.ball]
+        type = "sphere"
+        center = [0.0, 0.0, 0.0]
+        radius = 0.5
+        material = { type = "dummy" }
+
+        [shapes.loop]
+        type = "instance"
+        object = "loop"
+
+        [[objects]]
+        type = "instance"
+        object = "ball"
+        translate = [-1.0, 0.0, -2.0]
+
+        [[objects]]
+        type = "instance"
+        object = "ball"
+        translate = [1.0, 0.0, -2.0]
+        scale = [2.0, 2.0, 2.0]
+    "#;
+    let scene = parse_with_camera(source).unwrap();
+    let (_, world, _) = build_test_scene(&scene).unwrap();
+    let ray = crate::ray::Ray::new(Vec3::zeros(), Vec3::new(1., 0., -2.));
+    let rec = world.objects.hit(&ray, 0.001, Float::MAX).unwrap();
+    // 放大后半径为 1 的球面上距原点最近的点
+    let expected = Vec3::new(1., 0., -2.) * (1. - 1. / (5. as Float).sqrt());
+    assert!((rec.p - expected).length() < 1e-3);
+
+    let mut scene = scene;
+    scene.objects.push(ObjectDesc::Instance {
+        object: ShapeRef::Named("loop".to_string()),
+        translate: [0.; 3],
+        rotate: None,
+        scale: default_scale(),
+    });
+    match build_test_scene(&scene) {
+        Err(SceneFileError::RecursiveShape(name)) => assert_eq!(name, "loop"),
+        _ => panic!("expected a recursive shape error"),
+    }
+}
+
+#[test]
+fn test_conductor_presets() {
+    let source = r#"
+        [materials.gold]
+        type = "conductor"
+        ior = "gold"
+
+        [materials.custom]
+        type = "conductor"
+        ior = { eta = [0.2, 0.9, 1.1], k = [3.9, 2.5, 2.1] }
+        alpha = 0.2
+        distribution = "beckmann"
+    "#;
+    let scene = parse_with_camera(source).unwrap();
+    assert!(matches!(
+        scene.materials["gold"],
+        MaterialDesc::Conductor {
+            ior: ComplexIorDesc::Preset(ConductorPreset::Gold),
+            ..
+        }
+    ));
+    assert!(matches!(
+        scene.materials["custom"],
+        MaterialDesc::Conductor {
+            ior: ComplexIorDesc::Custom { .. },
+            distribution: Distribution::Beckmann,
+            ..
+        }
+    ));
+    assert!(build_test_scene(&scene).is_ok());
+    assert!(parse_with_camera(&source.replace("\"gold\"", "\"unobtainium\"")).is_err());
+}
+
+#[test]
+fn test_principled() {
+    let source = r#"
+        [materials.plastic]
+        type = "principled"
+        base_color = [0.8, 0.1, 0.1]
+        clearcoat = 1.0
+
+        [materials.default]
+        type = "principled"
+    "#;
+    let scene = parse_with_camera(source).unwrap();
+    match &scene.materials["plastic"] {
+        MaterialDesc::Principled(desc) => {
+            assert_eq!(desc.clearcoat, 1.);
+            assert_eq!(desc.roughness, 0.5);
+        }
+        _ => panic!("expected a principled material"),
+    }
+    assert!(build_test_scene(&scene).is_ok());
+    assert!(parse_with_camera(&source.replace("clearcoat =", "clearcoat_roughness =")).is_err());
+}
+
+#[test]
+fn test_referenced_files() {
+    let dir = std::env::temp_dir().join("toy-raytracer-rust-test-scene-files");
+    std::fs::create_dir_all(&dir).unwrap();
+    crate::image::write_png(dir.join("wood.png").to_str().unwrap(), 1, 1, &[128, 64, 0]).unwrap();
+    std::fs::write(dir.join("box.mtl"), "newmtl wood\nmap_Kd wood.png\n").unwrap();
+    std::fs::write(
+        dir.join("box.obj"),
+        "mtllib box.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nusemtl wood\nf 1 2 3\n",
+    )
+    .unwrap();
+    std::fs::write(
+        dir.join("scene.toml"),
+        with_camera(
+            r#"
+            [[objects]]
+            type = "obj"
+            path = "box.obj"
+
+            [[objects]]
+            type = "sphere"
+            center = [0.0, 0.0, -1.0]
+            radius = 0.5
+            material = { type = "lambertian", albedo = { type = "image", path = "wood.png" } }
+            "#,
+        ),
+    )
+    .unwrap();
+    let result = load_scene_file(dir.join("scene.toml"), 1.5, false);
+    std::fs::remove_dir_all(&dir).unwrap();
+    let (_, _, _, files) = result.unwrap();
+    let names: Vec<_> = files
+        .iter()
+        .map(|path| path.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(
+        names,
+        ["scene.toml", "box.obj", "box.mtl", "wood.png", "wood.png"]
+    );
+}
@@ -1,7 +1,9 @@
+use crate::aabb::Aabb;
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::AnyMaterial;
 use crate::ray::Ray;
-use crate::vec3::{Float, Vec3};
+use crate::sampler::Sampler;
+use crate::vec3::{floats, Float, Vec3};
 
 pub struct Sphere {
     pub center: Vec3,
@@ -27,28 +29,238 @@ impl Sphere {
     }
 }
 
+impl Sphere {
+    /// 单位球面上一点的经纬度坐标：u 自 -x 方向绕 y 轴逆时针取值，v 自南极至北极取值，均属于 [0, 1]
+    pub fn uv(p: &Vec3) -> (Float, Float) {
+        let theta = (-p.y).clamp(-1., 1.).acos();
+        let phi = (-p.z).atan2(p.x) + floats::consts::PI;
+        (phi / (2. * floats::consts::PI), theta / floats::consts::PI)
+    }
+}
+
 unsafe impl Sync for Sphere {}
 
+/// 球心位于 `center`、半径为 `radius` 的球面与光线的最近交点
+fn hit_sphere(
+    center: Vec3,
+    radius: Float,
+    material: &AnyMaterial,
+    ray: &Ray,
+    t_min: Float,
+    t_max: Float,
+) -> Option<HitRecord> {
+    let oc = ray.origin - center;
+    let a = ray.direction.length_squared();
+    let b_2 = oc.dot(&ray.direction);
+    let c = oc.length_squared() - radius.powi(2);
+    let d = b_2.powi(2) - a * c;
+    if d >= 0. {
+        // d == 0 是小概率事件
+        let d_sqrt = d.sqrt();
+        [(-b_2 - d_sqrt) / a, (-b_2 + d_sqrt) / a]
+            .into_iter()
+            .find(|t| t_min < *t && *t < t_max)
+            .map(|t| {
+                let mut rec =
+                    HitRecord::create(ray, t, material.clone(), |p| ((p - center) / radius).unit());
+                let p = (rec.p - center) / radius.abs();
+                (rec.u, rec.v) = Sphere::uv(&p);
+                // u 即绕 y 轴的方位角，两极处切线退化为零
+                rec.tangent = Vec3::new(p.z, 0., -p.x) * (2. * floats::consts::PI * radius.abs());
+                rec
+            })
+    } else {
+        None
+    }
+}
+
+/// 自球外一点看向球体的圆锥内均匀采样方向，球内一点则在整个单位球面上均匀采样
+fn sphere_pdf_value(center: Vec3, radius: Float, ray: &Ray) -> Float {
+    let to_center = center - ray.origin;
+    let distance_squared = to_center.length_squared();
+    let radius_squared = radius * radius;
+    if distance_squared <= radius_squared {
+        return 0.25 * floats::consts::FRAC_1_PI;
+    }
+    let cos_theta_max = (1. - radius_squared / distance_squared).sqrt();
+    let cos_theta = ray.direction.unit().dot(&to_center) / distance_squared.sqrt();
+    if cos_theta < cos_theta_max {
+        return 0.;
+    }
+    // 1 - cos_theta_max，避免远处的小球相减时损失精度
+    let one_minus_cos = radius_squared / distance_squared / (1. + cos_theta_max);
+    1. / (2. * floats::consts::PI * one_minus_cos)
+}
+
+fn sphere_random_direction(
+    center: Vec3,
+    radius: Float,
+    origin: &Vec3,
+    sampler: &mut dyn Sampler,
+) -> Vec3 {
+    let to_center = center - *origin;
+    let distance_squared = to_center.length_squared();
+    let radius_squared = radius * radius;
+    if distance_squared <= radius_squared {
+        return Vec3::random_unit_vector(sampler);
+    }
+    let cos_theta_max = (1. - radius_squared / distance_squared).sqrt();
+    let one_minus_cos = radius_squared / distance_squared / (1. + cos_theta_max);
+    let z = 1. - sampler.random() * one_minus_cos;
+    let phi = 2. * floats::consts::PI * sampler.random();
+    let r = (1. - z * z).max(0.).sqrt();
+    let local = Vec3::new(r * phi.cos(), r * phi.sin(), z);
+    to_center.unit().from_local(&local)
+}
+
+/// 半径为负的球体用于构造中空的玻璃球，其包围盒与同等大小的正半径球体相同
+fn sphere_bounds(center: Vec3, radius: Float) -> Aabb {
+    let r = Vec3::from(radius.abs());
+    Aabb::new(center - r, center + r)
+}
+
 impl Hittable for Sphere {
     fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
-        let oc = ray.origin - self.center;
-        let a = ray.direction.length_squared();
-        let b_2 = oc.dot(&ray.direction);
-        let c = oc.length_squared() - self.radius.powi(2);
-        let d = b_2.powi(2) - a * c;
-        if d >= 0. {
-            // d == 0 是小概率事件
-            let d_sqrt = d.sqrt();
-            vec![(-b_2 - d_sqrt) / a, (-b_2 + d_sqrt) / a]
-                .into_iter()
-                .find(|t| t_min < *t && *t < t_max)
-                .map(|t| {
-                    HitRecord::create(ray, t, self.material.clone(), |p| {
-                        ((p - self.center) / self.radius).unit()
-                    })
-                })
-        } else {
-            None
+        hit_sphere(self.center, self.radius, &self.material, ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        sphere_bounds(self.center, self.radius)
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> Float {
+        sphere_pdf_value(self.center, self.radius, ray)
+    }
+
+    fn random_direction(&self, origin: &Vec3, _time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        sphere_random_direction(self.center, self.radius, origin, sampler)
+    }
+}
+
+/// 球心在 `time0` 至 `time1` 间自 `center0` 匀速移动至 `center1` 的球体
+pub struct MovingSphere {
+    pub center0: Vec3,
+    pub center1: Vec3,
+    pub time0: Float,
+    pub time1: Float,
+    pub radius: Float,
+    pub material: AnyMaterial,
+}
+
+impl MovingSphere {
+    pub fn new(
+        center0: Vec3,
+        center1: Vec3,
+        time0: Float,
+        time1: Float,
+        radius: Float,
+        material: AnyMaterial,
+    ) -> MovingSphere {
+        MovingSphere {
+            center0,
+            center1,
+            time0,
+            time1,
+            radius,
+            material,
         }
     }
+
+    pub fn center(&self, time: Float) -> Vec3 {
+        if self.time1 == self.time0 {
+            return self.center0;
+        }
+        let s = (time - self.time0) / (self.time1 - self.time0);
+        self.center0 + (self.center1 - self.center0) * s
+    }
+}
+
+impl Hittable for MovingSphere {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        hit_sphere(
+            self.center(ray.t),
+            self.radius,
+            &self.material,
+            ray,
+            t_min,
+            t_max,
+        )
+    }
+
+    /// 覆盖 `time0` 至 `time1` 间扫过的范围，光线时间应位于该区间内
+    fn bounding_box(&self) -> Aabb {
+        sphere_bounds(self.center0, self.radius).union(&sphere_bounds(self.center1, self.radius))
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> Float {
+        sphere_pdf_value(self.center(ray.t), self.radius, ray)
+    }
+
+    fn random_direction(&self, origin: &Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        sphere_random_direction(self.center(time), self.radius, origin, sampler)
+    }
+}
+
+#[test]
+fn test_sphere_uv() {
+    let close =
+        |a: (Float, Float), b: (Float, Float)| (a.0 - b.0).abs() < 1e-6 && (a.1 - b.1).abs() < 1e-6;
+    assert!(close(Sphere::uv(&Vec3::new(1., 0., 0.)), (0.5, 0.5)));
+    assert!(close(Sphere::uv(&Vec3::new(0., 1., 0.)), (0.5, 1.)));
+    assert!(close(Sphere::uv(&Vec3::new(0., 0., 1.)), (0.25, 0.5)));
+    assert!(close(Sphere::uv(&Vec3::new(0., 0., -1.)), (0.75, 0.5)));
+}
+
+#[test]
+fn test_sphere_shading_basis() {
+    use crate::material::DummyMaterial;
+    use std::sync::Arc;
+
+    // 切线沿经线方向，在 z = 0 两侧连续，不随法线的任意正交基翻转
+    let sphere = Sphere::new(Vec3::zeros(), 1., Arc::new(DummyMaterial {}));
+    let direction = Vec3::new(-1., 0., 0.);
+    let bases: Vec<_> = [0.01, -0.01]
+        .into_iter()
+        .map(|z| {
+            let ray = Ray::new(Vec3::new(5., 0.2, z), direction);
+            let rec = sphere.hit(&ray, 0.001, Float::MAX).unwrap();
+            let (s, t) = rec.shading_basis();
+            assert!(s.dot(&rec.normal).abs() < 1e-5 && (s.cross(&t) - rec.normal).length() < 1e-5);
+            s
+        })
+        .collect();
+    assert!((bases[0] - bases[1]).length() < 0.05);
+    assert!(bases[0].z < -0.99);
+}
+
+#[test]
+fn test_moving_sphere() {
+    use crate::material::DummyMaterial;
+    use std::sync::Arc;
+
+    let sphere = MovingSphere::new(
+        Vec3::new(0., 0., 0.),
+        Vec3::new(0., 2., 0.),
+        0.,
+        1.,
+        0.5,
+        Arc::new(DummyMaterial {}),
+    );
+    let origin = Vec3::new(0., 0., 5.);
+    let direction = Vec3::new(0., 0., -1.);
+    assert!(sphere
+        .hit(&Ray::new_at(origin, direction, 0.), 0.001, Float::MAX)
+        .is_some());
+    assert!(sphere
+        .hit(&Ray::new_at(origin, direction, 1.), 0.001, Float::MAX)
+        .is_none());
+    let rec = sphere
+        .hit(
+            &Ray::new_at(Vec3::new(0., 1., 5.), direction, 0.5),
+            0.001,
+            Float::MAX,
+        )
+        .unwrap();
+    assert_eq!(rec.t, 4.5);
+    assert_eq!(sphere.bounding_box().max, Vec3::new(0.5, 2.5, 0.5));
 }
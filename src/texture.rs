@@ -0,0 +1,261 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::color::srgb_to_linear;
+use crate::image::{read_png, ImageError};
+use crate::math::Float;
+use crate::sampler::{RandomSampler, Sampler};
+use crate::vec3::Vec3;
+
+/// 纹理，按表面参数坐标 (u, v) 与交点位置 p 取值
+pub trait Texture: Send + Sync {
+    fn value(&self, u: Float, v: Float, p: &Vec3) -> Vec3;
+}
+
+pub type AnyTexture = Arc<dyn Texture>;
+
+/// 纯色
+#[derive(Debug, Clone)]
+pub struct SolidColor {
+    pub color: Vec3,
+}
+
+impl SolidColor {
+    pub fn new(r: Float, g: Float, b: Float) -> SolidColor {
+        SolidColor {
+            color: Vec3::new(r, g, b),
+        }
+    }
+}
+
+impl From<Vec3> for SolidColor {
+    fn from(color: Vec3) -> Self {
+        SolidColor { color }
+    }
+}
+
+impl Texture for SolidColor {
+    fn value(&self, _u: Float, _v: Float, _p: &Vec3) -> Vec3 {
+        self.color
+    }
+}
+
+/// 三维棋盘格，边长为 `scale` 的立方体交替取两种纹理
+pub struct CheckerTexture {
+    pub scale: Float,
+    pub even: AnyTexture,
+    pub odd: AnyTexture,
+}
+
+impl CheckerTexture {
+    pub fn new(scale: Float, even: AnyTexture, odd: AnyTexture) -> CheckerTexture {
+        CheckerTexture { scale, even, odd }
+    }
+}
+
+impl Texture for CheckerTexture {
+    fn value(&self, u: Float, v: Float, p: &Vec3) -> Vec3 {
+        let cell = p.apply(|x| (x / self.scale).floor());
+        if (cell.x as i64 + cell.y as i64 + cell.z as i64) % 2 == 0 {
+            self.even.value(u, v, p)
+        } else {
+            self.odd.value(u, v, p)
+        }
+    }
+}
+
+const PERLIN_POINT_COUNT: usize = 256;
+
+/// 以随机单位向量为梯度的 Perlin 噪声
+pub struct Perlin {
+    gradients: Vec<Vec3>,
+    perm_x: Vec<usize>,
+    perm_y: Vec<usize>,
+    perm_z: Vec<usize>,
+}
+
+impl Perlin {
+    pub fn new(sampler: &mut dyn Sampler) -> Perlin {
+        let gradients = (0..PERLIN_POINT_COUNT)
+            .map(|_| Vec3::random_between(sampler, -1., 1.).unit())
+            .collect();
+        Perlin {
+            gradients,
+            perm_x: Self::permutation(sampler),
+            perm_y: Self::permutation(sampler),
+            perm_z: Self::permutation(sampler),
+        }
+    }
+
+    fn permutation(sampler: &mut dyn Sampler) -> Vec<usize> {
+        let mut perm = (0..PERLIN_POINT_COUNT).collect::<Vec<usize>>();
+        for i in (1..PERLIN_POINT_COUNT).rev() {
+            let target = (sampler.random() * (i + 1) as Float) as usize;
+            perm.swap(i, target.min(i));
+        }
+        perm
+    }
+
+    /// 取值范围约为 [-1, 1]
+    pub fn noise(&self, p: &Vec3) -> Float {
+        let floor = p.apply(Float::floor);
+        let frac = *p - floor;
+        // Hermite 平滑
+        let w = frac.apply(|t| t * t * (3. - 2. * t));
+        let (i, j, k) = (floor.x as i64, floor.y as i64, floor.z as i64);
+        let mask = PERLIN_POINT_COUNT as i64 - 1;
+
+        let mut accum = 0.;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let index = self.perm_x[((i + di) & mask) as usize]
+                        ^ self.perm_y[((j + dj) & mask) as usize]
+                        ^ self.perm_z[((k + dk) & mask) as usize];
+                    let (fi, fj, fk) = (di as Float, dj as Float, dk as Float);
+                    let weight = frac - Vec3::new(fi, fj, fk);
+                    accum += (fi * w.x + (1. - fi) * (1. - w.x))
+                        * (fj * w.y + (1. - fj) * (1. - w.y))
+                        * (fk * w.z + (1. - fk) * (1. - w.z))
+                        * self.gradients[index].dot(&weight);
+                }
+            }
+        }
+        accum
+    }
+
+    /// 湍流：频率逐级加倍、振幅逐级减半的噪声之和的绝对值
+    pub fn turbulence(&self, p: &Vec3, depth: usize) -> Float {
+        let mut accum = 0.;
+        let mut p = *p;
+        let mut weight = 1.;
+        for _ in 0..depth {
+            accum += weight * self.noise(&p);
+            weight *= 0.5;
+            p = p * 2.;
+        }
+        accum.abs()
+    }
+}
+
+impl Default for Perlin {
+    fn default() -> Self {
+        Perlin::new(&mut RandomSampler::new(0))
+    }
+}
+
+/// 以湍流扰动相位的正弦条纹，形似大理石
+pub struct MarbleTexture {
+    pub noise: Perlin,
+    pub scale: Float,
+    pub color: Vec3,
+}
+
+impl MarbleTexture {
+    /// 噪声的梯度与排列由 `sampler` 生成
+    pub fn new(scale: Float, color: Vec3, sampler: &mut dyn Sampler) -> MarbleTexture {
+        MarbleTexture {
+            noise: Perlin::new(sampler),
+            scale,
+            color,
+        }
+    }
+}
+
+impl Texture for MarbleTexture {
+    fn value(&self, _u: Float, _v: Float, p: &Vec3) -> Vec3 {
+        let phase = self.scale * p.z + 10. * self.noise.turbulence(p, 7);
+        self.color * (0.5 * (1. + phase.sin()))
+    }
+}
+
+/// 图像纹理，u 自左向右、v 自下向上取值于 [0, 1]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    /// 线性空间的像素颜色，逐行自上而下排列
+    pixels: Vec<Vec3>,
+}
+
+impl ImageTexture {
+    /// 读取 PNG 图像，像素值按 sRGB 编码转换到线性空间
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<ImageTexture, ImageError> {
+        let (width, height, data) = read_png(path)?;
+        let pixels = data
+            .chunks_exact(3)
+            .map(|p| Vec3::new(p[0] as Float, p[1] as Float, p[2] as Float) / 255.)
+            .map(|c| c.apply(srgb_to_linear))
+            .collect();
+        Ok(ImageTexture {
+            width,
+            height,
+            pixels,
+        })
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, u: Float, v: Float, _p: &Vec3) -> Vec3 {
+        if self.pixels.is_empty() {
+            // 用醒目的青色标记缺失的纹理
+            return Vec3::new(0., 1., 1.);
+        }
+        let u = u.clamp(0., 1.);
+        let v = 1. - v.clamp(0., 1.);
+        let x = ((u * self.width as Float) as usize).min(self.width - 1);
+        let y = ((v * self.height as Float) as usize).min(self.height - 1);
+        self.pixels[y * self.width + x]
+    }
+}
+
+#[test]
+fn test_checker_texture() {
+    let white: AnyTexture = Arc::new(SolidColor::new(1., 1., 1.));
+    let black: AnyTexture = Arc::new(SolidColor::new(0., 0., 0.));
+    let checker = CheckerTexture::new(1., white, black);
+    assert_eq!(
+        checker.value(0., 0., &Vec3::new(0.5, 0.5, 0.5)),
+        Vec3::ones()
+    );
+    assert_eq!(
+        checker.value(0., 0., &Vec3::new(1.5, 0.5, 0.5)),
+        Vec3::zeros()
+    );
+    assert_eq!(
+        checker.value(0., 0., &Vec3::new(-0.5, 0.5, 0.5)),
+        Vec3::zeros()
+    );
+    assert_eq!(
+        checker.value(0., 0., &Vec3::new(-0.5, -0.5, 0.5)),
+        Vec3::ones()
+    );
+}
+
+#[test]
+fn test_perlin_range() {
+    let mut sampler = RandomSampler::new(0);
+    let perlin = Perlin::new(&mut sampler);
+    for _ in 0..1000 {
+        let p = Vec3::random_between(&mut sampler, -10., 10.);
+        assert!(perlin.noise(&p).abs() <= 1.1);
+    }
+    // 整数格点处梯度与偏移的点积为零
+    assert_eq!(perlin.noise(&Vec3::new(3., -2., 5.)), 0.);
+}
+
+#[test]
+fn test_image_texture() {
+    let path = std::env::temp_dir().join("toy-raytracer-rust-test-image-texture.png");
+    let pixels = [255, 0, 0, 0, 0, 255];
+    crate::image::write_png(path.to_str().unwrap(), 2, 1, &pixels).unwrap();
+    let texture = ImageTexture::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(
+        texture.value(0.25, 0.5, &Vec3::zeros()),
+        Vec3::new(1., 0., 0.)
+    );
+    assert_eq!(
+        texture.value(0.75, 0.5, &Vec3::zeros()),
+        Vec3::new(0., 0., 1.)
+    );
+}
@@ -0,0 +1,142 @@
+//! 将图像划分为矩形分块并确定渲染顺序
+
+use std::str::FromStr;
+
+use crate::math::Float;
+
+/// 图像中 [x0, x1) × [y0, y1) 范围内的像素
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Tile {
+    pub fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    pub fn area(&self) -> usize {
+        self.width() * self.height()
+    }
+
+    /// 逐行遍历分块内的像素坐标
+    pub fn pixels(&self) -> impl Iterator<Item = (usize, usize)> {
+        let (x0, x1) = (self.x0, self.x1);
+        (self.y0..self.y1).flat_map(move |y| (x0..x1).map(move |x| (x, y)))
+    }
+}
+
+/// 分块的渲染顺序
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TileOrder {
+    /// 从左到右、从上到下
+    Scanline,
+    /// 从图像中心一圈圈向外
+    Spiral,
+    /// 沿 Hilbert 曲线，相邻分块在空间中也相邻
+    Hilbert,
+}
+
+impl FromStr for TileOrder {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "scanline" => Ok(TileOrder::Scanline),
+            "spiral" => Ok(TileOrder::Spiral),
+            "hilbert" => Ok(TileOrder::Hilbert),
+            _ => Err(format!("Unknown tile order {}", s)),
+        }
+    }
+}
+
+/// 将 `width` × `height` 的图像切分为边长 `size` 的分块，按 `order` 排列；右侧与底部的分块可能较小
+pub fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let (nx, ny) = (width.div_ceil(size), height.div_ceil(size));
+    let mut coords: Vec<(usize, usize)> =
+        (0..ny).flat_map(|y| (0..nx).map(move |x| (x, y))).collect();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let (cx, cy) = ((nx as Float - 1.) / 2., (ny as Float - 1.) / 2.);
+            let key = |&(x, y): &(usize, usize)| {
+                let (dx, dy) = (x as Float - cx, y as Float - cy);
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            coords.sort_by(|a, b| {
+                let (ka, kb) = (key(a), key(b));
+                ka.0.total_cmp(&kb.0).then(ka.1.total_cmp(&kb.1))
+            });
+        }
+        TileOrder::Hilbert => {
+            let n = nx.max(ny).next_power_of_two();
+            coords.sort_by_key(|&(x, y)| hilbert_index(n, x, y));
+        }
+    }
+    coords
+        .into_iter()
+        .map(|(x, y)| Tile {
+            x0: x * size,
+            y0: y * size,
+            x1: ((x + 1) * size).min(width),
+            y1: ((y + 1) * size).min(height),
+        })
+        .collect()
+}
+
+/// (x, y) 在边长为 `n`（2 的幂）的 Hilbert 曲线上的序号
+fn hilbert_index(n: usize, mut x: usize, mut y: usize) -> usize {
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = (x & s > 0) as usize;
+        let ry = (y & s > 0) as usize;
+        d += s * s * ((3 * rx) ^ ry);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+#[test]
+fn test_tiles_cover_image() {
+    for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+        let tiles = tiles(37, 21, 8, order);
+        assert_eq!(tiles.len(), 5 * 3);
+        let mut covered = vec![0; 37 * 21];
+        for tile in &tiles {
+            for (x, y) in tile.pixels() {
+                covered[y * 37 + x] += 1;
+            }
+        }
+        assert!(covered.iter().all(|&c| c == 1));
+    }
+    // 螺旋顺序从中心的分块开始
+    let spiral = tiles(40, 40, 8, TileOrder::Spiral);
+    assert_eq!((spiral[0].x0, spiral[0].y0), (16, 16));
+}
+
+#[test]
+fn test_hilbert_order() {
+    // 相邻的分块在空间中也相邻
+    let tiles = tiles(64, 64, 8, TileOrder::Hilbert);
+    for pair in tiles.windows(2) {
+        let dx = pair[0].x0.abs_diff(pair[1].x0);
+        let dy = pair[0].y0.abs_diff(pair[1].y0);
+        assert_eq!(dx + dy, 8);
+    }
+}
@@ -0,0 +1,168 @@
+//! 输出阶段的曝光调整与色调映射，将线性的高动态范围数值压缩至 [0, 1]
+
+use std::str::FromStr;
+
+use crate::color::linear_to_srgb;
+use crate::math::Float;
+use crate::vec3::Vec3;
+
+/// 色调映射算子
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ToneMap {
+    /// 直接截断至 [0, 1]
+    Clamp,
+    /// 按亮度压缩：L / (1 + L)
+    Reinhard,
+    /// 按亮度压缩，亮度为白点时映射为 1：L (1 + L / W²) / (1 + L)
+    ExtendedReinhard,
+    /// Uncharted 2 中使用的 Hable 曲线
+    Hable,
+    /// Narkowicz 对 ACES 电影曲线的拟合
+    Aces,
+}
+
+impl FromStr for ToneMap {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clamp" => Ok(ToneMap::Clamp),
+            "reinhard" => Ok(ToneMap::Reinhard),
+            "extended-reinhard" => Ok(ToneMap::ExtendedReinhard),
+            "hable" | "uncharted" => Ok(ToneMap::Hable),
+            "aces" => Ok(ToneMap::Aces),
+            _ => Err(format!("Unknown tone map {}", s)),
+        }
+    }
+}
+
+/// 曝光调整、色调映射与 sRGB 编码
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ToneMapping {
+    pub operator: ToneMap,
+    /// 曝光补偿，单位为档，每增加一档亮度加倍
+    pub exposure: Float,
+    /// Extended Reinhard 映射为纯白的亮度
+    pub white_point: Float,
+}
+
+impl Default for ToneMapping {
+    fn default() -> Self {
+        ToneMapping::new(ToneMap::Clamp)
+    }
+}
+
+impl ToneMapping {
+    pub fn new(operator: ToneMap) -> ToneMapping {
+        ToneMapping {
+            operator,
+            exposure: 0.,
+            white_point: 4.,
+        }
+    }
+
+    pub fn with_exposure(mut self, exposure: Float) -> ToneMapping {
+        self.exposure = exposure;
+        self
+    }
+
+    pub fn with_white_point(mut self, white_point: Float) -> ToneMapping {
+        self.white_point = white_point;
+        self
+    }
+
+    /// 线性场景值映射为 [0, 1] 内的线性显示值
+    pub fn apply(&self, color: Vec3) -> Vec3 {
+        let color = color.apply(|c| c.max(0.)) * self.exposure.exp2();
+        let mapped = match self.operator {
+            ToneMap::Clamp => color,
+            ToneMap::Reinhard => scale_luminance(color, |l| l / (1. + l)),
+            ToneMap::ExtendedReinhard => {
+                let white_squared = self.white_point * self.white_point;
+                scale_luminance(color, |l| l * (1. + l / white_squared) / (1. + l))
+            }
+            ToneMap::Hable => {
+                // 以线性白点 11.2 归一化，并预先提高两档曝光
+                const WHITE: Float = 11.2;
+                color.apply(|c| hable(2. * c) / hable(WHITE))
+            }
+            ToneMap::Aces => color.apply(|c| {
+                // 拟合曲线以 0.6 倍的输入匹配原始曲线的亮度
+                let x = 0.6 * c;
+                x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)
+            }),
+        };
+        mapped.apply(|c| c.clamp(0., 1.))
+    }
+
+    /// 色调映射后经 sRGB 传递函数编码为 8 位 RGB
+    pub fn to_srgb8(&self, color: Vec3) -> [u8; 3] {
+        let (r, g, b) = self.apply(color).apply(linear_to_srgb).into_color().as_u8();
+        [r, g, b]
+    }
+}
+
+/// Rec. 709 相对亮度
+pub fn luminance(color: &Vec3) -> Float {
+    0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z
+}
+
+/// 压缩亮度并保持色相，避免逐通道压缩导致的褪色
+fn scale_luminance<F: Fn(Float) -> Float>(color: Vec3, f: F) -> Vec3 {
+    let l = luminance(&color);
+    if l <= 0. {
+        color
+    } else {
+        color * (f(l) / l)
+    }
+}
+
+fn hable(x: Float) -> Float {
+    const A: Float = 0.15;
+    const B: Float = 0.50;
+    const C: Float = 0.10;
+    const D: Float = 0.20;
+    const E: Float = 0.02;
+    const F: Float = 0.30;
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+#[test]
+fn test_tone_map_range() {
+    let operators = [
+        ToneMap::Clamp,
+        ToneMap::Reinhard,
+        ToneMap::ExtendedReinhard,
+        ToneMap::Hable,
+        ToneMap::Aces,
+    ];
+    for operator in operators {
+        let mapping = ToneMapping::new(operator);
+        assert!(mapping.apply(Vec3::zeros()).length() < 1e-3);
+        // 灰度输入单调不减且不超过 1
+        let mut previous = 0.;
+        for i in 0..=200 {
+            let c = mapping.apply(Vec3::from(i as Float * 0.1)).x;
+            assert!(previous - 1e-6 <= c && c <= 1.);
+            previous = c;
+        }
+        // 亮部不再被截断为同一数值
+        if operator != ToneMap::Clamp {
+            let a = mapping.apply(Vec3::from(2.)).x;
+            let b = mapping.apply(Vec3::from(3.)).x;
+            assert!(a < b);
+        }
+    }
+}
+
+#[test]
+fn test_tone_map_exposure() {
+    let mapping = ToneMapping::new(ToneMap::Clamp).with_exposure(1.);
+    assert!((mapping.apply(Vec3::from(0.25)).x - 0.5).abs() < 1e-6);
+    let mapping = ToneMapping::new(ToneMap::ExtendedReinhard).with_white_point(2.);
+    assert!((mapping.apply(Vec3::from(2.)).x - 1.).abs() < 1e-6);
+    let mapping = ToneMapping::new(ToneMap::Reinhard);
+    assert!((mapping.apply(Vec3::from(1.)).x - 0.5).abs() < 1e-6);
+    assert_eq!(ToneMapping::default().to_srgb8(Vec3::from(1.)), [255; 3]);
+    assert_eq!(ToneMapping::default().to_srgb8(Vec3::zeros()), [0; 3]);
+}
@@ -0,0 +1,458 @@
+use std::sync::Arc;
+
+use crate::aabb::Aabb;
+use crate::bvh::Bvh;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::AnyMaterial;
+use crate::ray::Ray;
+use crate::sampler::Sampler;
+use crate::vec3::{Float, Vec3};
+
+/// Möller–Trumbore 求交，返回光线参数 t 与交点相对 `p1`、`p2` 的重心坐标
+fn intersect(
+    p0: &Vec3,
+    p1: &Vec3,
+    p2: &Vec3,
+    ray: &Ray,
+    t_min: Float,
+    t_max: Float,
+) -> Option<(Float, Float, Float)> {
+    let e1 = *p1 - *p0;
+    let e2 = *p2 - *p0;
+    let pvec = ray.direction.cross(&e2);
+    let det = e1.dot(&pvec);
+    if det == 0. {
+        // 光线与三角形所在平面平行
+        return None;
+    }
+    let inv_det = 1. / det;
+    let tvec = ray.origin - *p0;
+    let b1 = tvec.dot(&pvec) * inv_det;
+    if !(0. ..=1.).contains(&b1) {
+        return None;
+    }
+    let qvec = tvec.cross(&e1);
+    let b2 = ray.direction.dot(&qvec) * inv_det;
+    if b2 < 0. || b1 + b2 > 1. {
+        return None;
+    }
+    let t = e2.dot(&qvec) * inv_det;
+    if t_min < t && t < t_max {
+        Some((t, b1, b2))
+    } else {
+        None
+    }
+}
+
+fn triangle_bounds(p0: &Vec3, p1: &Vec3, p2: &Vec3) -> Aabb {
+    Aabb::empty().include(p0).include(p1).include(p2)
+}
+
+fn triangle_area(p0: &Vec3, p1: &Vec3, p2: &Vec3) -> Float {
+    0.5 * (*p1 - *p0).cross(&(*p2 - *p0)).length()
+}
+
+/// 在三角形上按面积均匀采样时，得到 `ray.direction` 的立体角概率密度
+fn triangle_pdf_value(p0: &Vec3, p1: &Vec3, p2: &Vec3, ray: &Ray) -> Float {
+    match intersect(p0, p1, p2, ray, 0.001, Float::MAX) {
+        Some((t, _, _)) => {
+            // 距离的平方除以面积与余弦之积，其中 |n| 为面积的两倍
+            let normal = (*p1 - *p0).cross(&(*p2 - *p0));
+            let cosine = normal.dot(&ray.direction).abs();
+            2. * t * t * ray.direction.length_squared() * ray.direction.length() / cosine
+        }
+        None => 0.,
+    }
+}
+
+/// 自 `origin` 指向三角形上按面积均匀分布的随机一点
+fn triangle_random_direction(
+    p0: &Vec3,
+    p1: &Vec3,
+    p2: &Vec3,
+    origin: &Vec3,
+    sampler: &mut dyn Sampler,
+) -> Vec3 {
+    let r1 = sampler.random().sqrt();
+    let r2 = sampler.random();
+    let point = *p0 * (1. - r1) + *p1 * (r1 * (1. - r2)) + *p2 * (r1 * r2);
+    point - *origin
+}
+
+/// 单个三角形，顶点按逆时针顺序给出时法线朝向观察者
+pub struct Triangle {
+    pub vertices: [Vec3; 3],
+    pub material: AnyMaterial,
+}
+
+impl Triangle {
+    pub fn new(p0: Vec3, p1: Vec3, p2: Vec3, material: AnyMaterial) -> Triangle {
+        Triangle {
+            vertices: [p0, p1, p2],
+            material,
+        }
+    }
+}
+
+impl Hittable for Triangle {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let [p0, p1, p2] = &self.vertices;
+        intersect(p0, p1, p2, ray, t_min, t_max).map(|(t, b1, b2)| {
+            let normal = (*p1 - *p0).cross(&(*p2 - *p0));
+            let mut rec = HitRecord::create(ray, t, self.material.clone(), |_| normal);
+            rec.u = b1;
+            rec.v = b2;
+            rec.tangent = *p1 - *p0;
+            rec
+        })
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let [p0, p1, p2] = &self.vertices;
+        triangle_bounds(p0, p1, p2)
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> Float {
+        let [p0, p1, p2] = &self.vertices;
+        triangle_pdf_value(p0, p1, p2, ray)
+    }
+
+    fn random_direction(&self, origin: &Vec3, _time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let [p0, p1, p2] = &self.vertices;
+        triangle_random_direction(p0, p1, p2, origin, sampler)
+    }
+}
+
+/// 网格中的一个面，各属性的下标分别指向 `TriangleMesh` 中对应的缓冲区
+#[derive(Debug, Clone, Copy)]
+pub struct MeshFace {
+    pub positions: [usize; 3],
+    pub normals: Option<[usize; 3]>,
+    pub uvs: Option<[usize; 3]>,
+    /// 指向 `TriangleMesh::materials` 的下标
+    pub material: usize,
+}
+
+struct MeshData {
+    positions: Vec<Vec3>,
+    normals: Vec<Vec3>,
+    uvs: Vec<(Float, Float)>,
+    materials: Vec<AnyMaterial>,
+}
+
+/// 网格内部的三角形，与同一网格的其他三角形共享顶点、法线与纹理坐标缓冲区
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    face: MeshFace,
+}
+
+impl MeshTriangle {
+    fn points(&self) -> (&Vec3, &Vec3, &Vec3) {
+        let [i0, i1, i2] = self.face.positions;
+        let positions = &self.mesh.positions;
+        (&positions[i0], &positions[i1], &positions[i2])
+    }
+}
+
+impl Hittable for MeshTriangle {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        let (p0, p1, p2) = self.points();
+        let (t, b1, b2) = intersect(p0, p1, p2, ray, t_min, t_max)?;
+        let b0 = 1. - b1 - b2;
+        let geometric = (*p1 - *p0).cross(&(*p2 - *p0));
+        let material = self.mesh.materials[self.face.material].clone();
+        let mut rec = HitRecord::create(ray, t, material, |_| geometric);
+
+        if let Some([n0, n1, n2]) = self.face.normals {
+            let normals = &self.mesh.normals;
+            let shading = normals[n0] * b0 + normals[n1] * b1 + normals[n2] * b2;
+            if shading.length_squared() > 0. {
+                // 着色法线与几何法线位于同侧，正反面仍以几何法线判断
+                let shading = if shading.dot(&geometric) < 0. {
+                    -shading.unit()
+                } else {
+                    shading.unit()
+                };
+                rec.normal = if rec.front_face { shading } else { -shading };
+            }
+        }
+
+        rec.tangent = *p1 - *p0;
+        (rec.u, rec.v) = match self.face.uvs {
+            Some([t0, t1, t2]) => {
+                let uvs = &self.mesh.uvs;
+                // 由两条边上的纹理坐标差解出 ∂p/∂u，纹理坐标退化时保留第一条边
+                let (du1, dv1) = (uvs[t1].0 - uvs[t0].0, uvs[t1].1 - uvs[t0].1);
+                let (du2, dv2) = (uvs[t2].0 - uvs[t0].0, uvs[t2].1 - uvs[t0].1);
+                let det = du1 * dv2 - dv1 * du2;
+                if det.abs() > 1e-8 {
+                    rec.tangent = ((*p1 - *p0) * dv2 - (*p2 - *p0) * dv1) / det;
+                }
+                (
+                    uvs[t0].0 * b0 + uvs[t1].0 * b1 + uvs[t2].0 * b2,
+                    uvs[t0].1 * b0 + uvs[t1].1 * b1 + uvs[t2].1 * b2,
+                )
+            }
+            None => (b1, b2),
+        };
+        Some(rec)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        let (p0, p1, p2) = self.points();
+        triangle_bounds(p0, p1, p2)
+    }
+
+    fn pdf_value(&self, ray: &Ray) -> Float {
+        let (p0, p1, p2) = self.points();
+        triangle_pdf_value(p0, p1, p2, ray)
+    }
+
+    fn random_direction(&self, origin: &Vec3, _time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let (p0, p1, p2) = self.points();
+        triangle_random_direction(p0, p1, p2, origin, sampler)
+    }
+}
+
+/// 索引三角形网格，内部以层次包围盒组织各个三角形
+pub struct TriangleMesh {
+    bvh: Bvh,
+    triangles: Vec<Arc<MeshTriangle>>,
+    /// 各三角形面积的前缀和，用于按面积采样光源
+    area_cdf: Vec<Float>,
+}
+
+impl TriangleMesh {
+    pub fn new(
+        positions: Vec<Vec3>,
+        normals: Vec<Vec3>,
+        uvs: Vec<(Float, Float)>,
+        materials: Vec<AnyMaterial>,
+        faces: Vec<MeshFace>,
+    ) -> TriangleMesh {
+        let mesh = Arc::new(MeshData {
+            positions,
+            normals,
+            uvs,
+            materials,
+        });
+        let triangles = faces
+            .into_iter()
+            .map(|face| {
+                Arc::new(MeshTriangle {
+                    mesh: mesh.clone(),
+                    face,
+                })
+            })
+            .collect::<Vec<_>>();
+        let area_cdf = triangles
+            .iter()
+            .scan(0., |total, triangle| {
+                let (p0, p1, p2) = triangle.points();
+                *total += triangle_area(p0, p1, p2);
+                Some(*total)
+            })
+            .collect();
+        let hittables = triangles
+            .iter()
+            .map(|triangle| triangle.clone() as Arc<dyn Hittable>)
+            .collect();
+        TriangleMesh {
+            bvh: Bvh::new(hittables),
+            triangles,
+            area_cdf,
+        }
+    }
+
+    /// 只有顶点位置、所有面使用同一材质的网格
+    pub fn with_material(
+        positions: Vec<Vec3>,
+        indices: Vec<[usize; 3]>,
+        material: AnyMaterial,
+    ) -> TriangleMesh {
+        let faces = indices
+            .into_iter()
+            .map(|positions| MeshFace {
+                positions,
+                normals: None,
+                uvs: None,
+                material: 0,
+            })
+            .collect();
+        TriangleMesh::new(positions, vec![], vec![], vec![material], faces)
+    }
+
+    /// 以 `q` 为一角、`u` 与 `v` 为两边的平行四边形，法线方向为 `u × v`
+    pub fn quad(q: Vec3, u: Vec3, v: Vec3, material: AnyMaterial) -> TriangleMesh {
+        TriangleMesh::with_material(
+            vec![q, q + u, q + u + v, q + v],
+            vec![[0, 1, 2], [0, 2, 3]],
+            material,
+        )
+    }
+
+    /// 以 `a`、`b` 为对角顶点的轴对齐长方体，各面法线朝外
+    pub fn cuboid(a: Vec3, b: Vec3, material: AnyMaterial) -> TriangleMesh {
+        let min = a.element_wise(Float::min, &b);
+        let max = a.element_wise(Float::max, &b);
+        // 下标的三个二进制位依次选择 x、y、z 取最小值还是最大值
+        let positions = (0..8)
+            .map(|i| {
+                Vec3::new(
+                    if i & 1 == 0 { min.x } else { max.x },
+                    if i & 2 == 0 { min.y } else { max.y },
+                    if i & 4 == 0 { min.z } else { max.z },
+                )
+            })
+            .collect();
+        let faces = [
+            [0, 4, 6, 2], // -x
+            [1, 3, 7, 5], // +x
+            [0, 1, 5, 4], // -y
+            [2, 6, 7, 3], // +y
+            [0, 2, 3, 1], // -z
+            [4, 5, 7, 6], // +z
+        ];
+        let indices = faces
+            .iter()
+            .flat_map(|f| [[f[0], f[1], f[2]], [f[0], f[2], f[3]]])
+            .collect();
+        TriangleMesh::with_material(positions, indices, material)
+    }
+}
+
+impl Hittable for TriangleMesh {
+    fn hit(&self, ray: &Ray, t_min: Float, t_max: Float) -> Option<HitRecord> {
+        self.bvh.hit(ray, t_min, t_max)
+    }
+
+    fn bounding_box(&self) -> Aabb {
+        self.bvh.bounding_box()
+    }
+
+    /// 逐个三角形累加，只适合三角形不多的光源
+    fn pdf_value(&self, ray: &Ray) -> Float {
+        let total = match self.area_cdf.last() {
+            Some(total) if *total > 0. => *total,
+            _ => return 0.,
+        };
+        self.triangles
+            .iter()
+            .map(|triangle| {
+                let (p0, p1, p2) = triangle.points();
+                triangle_area(p0, p1, p2) * triangle.pdf_value(ray)
+            })
+            .sum::<Float>()
+            / total
+    }
+
+    fn random_direction(&self, origin: &Vec3, time: Float, sampler: &mut dyn Sampler) -> Vec3 {
+        let total = match self.area_cdf.last() {
+            Some(total) => *total,
+            None => return Vec3::new(1., 0., 0.),
+        };
+        let target = sampler.random() * total;
+        let index = self
+            .area_cdf
+            .partition_point(|area| *area <= target)
+            .min(self.triangles.len() - 1);
+        self.triangles[index].random_direction(origin, time, sampler)
+    }
+}
+
+#[test]
+fn test_triangle_hit() {
+    use crate::material::DummyMaterial;
+
+    let triangle = Triangle::new(
+        Vec3::new(0., 0., 0.),
+        Vec3::new(1., 0., 0.),
+        Vec3::new(0., 1., 0.),
+        Arc::new(DummyMaterial {}),
+    );
+    let ray = Ray::new(Vec3::new(0.25, 0.5, 1.), Vec3::new(0., 0., -1.));
+    let rec = triangle.hit(&ray, 0.001, Float::MAX).unwrap();
+    assert_eq!(rec.t, 1.);
+    assert_eq!((rec.u, rec.v), (0.25, 0.5));
+    assert!(rec.front_face);
+    assert_eq!(rec.normal, Vec3::new(0., 0., 1.));
+
+    let miss = Ray::new(Vec3::new(0.75, 0.5, 1.), Vec3::new(0., 0., -1.));
+    assert!(triangle.hit(&miss, 0.001, Float::MAX).is_none());
+}
+
+#[test]
+fn test_cuboid_normals_face_outwards() {
+    use crate::material::DummyMaterial;
+
+    let cuboid = TriangleMesh::cuboid(Vec3::zeros(), Vec3::ones(), Arc::new(DummyMaterial {}));
+    let center = Vec3::from(0.5);
+    let directions = [
+        Vec3::new(1., 0., 0.),
+        Vec3::new(0., 1., 0.),
+        Vec3::new(0., 0., 1.),
+    ];
+    for d in directions.iter().flat_map(|d| [*d, -*d]) {
+        let ray = Ray::new(center + d * 2., -d);
+        let rec = cuboid.hit(&ray, 0.001, Float::MAX).unwrap();
+        assert!(rec.front_face);
+        assert_eq!(rec.normal, d);
+    }
+}
+
+#[test]
+fn test_mesh_smooth_normal() {
+    use crate::material::DummyMaterial;
+
+    let positions = vec![
+        Vec3::new(0., 0., 0.),
+        Vec3::new(1., 0., 0.),
+        Vec3::new(0., 1., 0.),
+    ];
+    let normals = vec![
+        Vec3::new(0., 0., 1.),
+        Vec3::new(1., 0., 1.).unit(),
+        Vec3::new(0., 1., 1.).unit(),
+    ];
+    let face = MeshFace {
+        positions: [0, 1, 2],
+        normals: Some([0, 1, 2]),
+        uvs: None,
+        material: 0,
+    };
+    let mesh = TriangleMesh::new(
+        positions,
+        normals.clone(),
+        vec![],
+        vec![Arc::new(DummyMaterial {})],
+        vec![face],
+    );
+    let ray = Ray::new(Vec3::new(0.5, 0.5, -1.), Vec3::new(0., 0., 1.));
+    let rec = mesh.hit(&ray, 0.001, Float::MAX).unwrap();
+    assert!(!rec.front_face);
+    let expected = -(normals[1] * 0.5 + normals[2] * 0.5).unit();
+    assert!((rec.normal - expected).length() < 1e-6);
+}
+
+#[test]
+fn test_quad_light_pdf() {
+    use crate::material::DummyMaterial;
+
+    // 位于正方形光源下方 h 处，垂直指向光源方向的概率密度为 h² / A
+    let quad = TriangleMesh::quad(
+        Vec3::new(-1., 2., -1.),
+        Vec3::new(2., 0., 0.),
+        Vec3::new(0., 0., 2.),
+        Arc::new(DummyMaterial {}),
+    );
+    let origin = Vec3::new(0.5, 0., -0.25);
+    let up = Ray::new(origin, Vec3::new(0., 1., 0.));
+    assert!((quad.pdf_value(&up) - 1.).abs() < 1e-5);
+    let side = Ray::new(origin, Vec3::new(1., 0., 0.));
+    assert_eq!(quad.pdf_value(&side), 0.);
+    let mut sampler = crate::sampler::RandomSampler::new(0);
+    for _ in 0..100 {
+        let direction = quad.random_direction(&origin, 0., &mut sampler);
+        assert!(quad.pdf_value(&Ray::new(origin, direction)) > 0.);
+    }
+}
@@ -1,5 +1,6 @@
 use crate::color::Color;
-pub use crate::math::{floats, random, random_between, Float};
+pub use crate::math::{floats, Float};
+use crate::sampler::Sampler;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Vec3 {
@@ -33,42 +34,35 @@ impl Vec3 {
         Vec3 { x, y, z }
     }
 
-    pub fn random() -> Self {
-        let x = random();
-        let y = random();
-        let z = random();
+    pub fn random(sampler: &mut dyn Sampler) -> Self {
+        let x = sampler.random();
+        let y = sampler.random();
+        let z = sampler.random();
         Vec3::new(x, y, z)
     }
 
-    pub fn random_between(min: Float, max: Float) -> Self {
-        let x = random_between(min, max);
-        let y = random_between(min, max);
-        let z = random_between(min, max);
+    pub fn random_between(sampler: &mut dyn Sampler, min: Float, max: Float) -> Self {
+        let x = sampler.random_between(min, max);
+        let y = sampler.random_between(min, max);
+        let z = sampler.random_between(min, max);
         Vec3::new(x, y, z)
     }
 
-    pub fn random_in_unit_sphere() -> Self {
-        loop {
-            let p = Self::random_between(-1., 1.);
-            if p.length() < 1. {
-                return p;
-            }
-        }
+    /// 单位球内的均匀分布，固定占用采样器的三维
+    pub fn random_in_unit_sphere(sampler: &mut dyn Sampler) -> Self {
+        let direction = Self::random_unit_vector(sampler);
+        direction * sampler.random().cbrt()
     }
 
-    pub fn random_in_unit_disk() -> Self {
-        loop {
-            let x = random_between(-1.0, 1.0);
-            let y = random_between(-1.0, 1.0);
-            let p = Vec3::new(x, y, 0.0);
-            if p.length_squared() < 1.0 {
-                return p;
-            }
-        }
+    /// 单位圆盘内的均匀分布，固定占用采样器的二维
+    pub fn random_in_unit_disk(sampler: &mut dyn Sampler) -> Self {
+        let r = sampler.random().sqrt();
+        let theta = sampler.random_between(0., 2. * floats::consts::PI);
+        Vec3::new(r * theta.cos(), r * theta.sin(), 0.)
     }
 
-    pub fn random_in_hemisphere(normal: &Vec3) -> Self {
-        let p = Self::random_in_unit_sphere();
+    pub fn random_in_hemisphere(sampler: &mut dyn Sampler, normal: &Vec3) -> Self {
+        let p = Self::random_in_unit_sphere(sampler);
         if p.dot(normal) > 0. {
             p
         } else {
@@ -76,9 +70,9 @@ impl Vec3 {
         }
     }
 
-    pub fn random_unit_vector() -> Self {
-        let t = random_between(0., 2. * floats::consts::PI);
-        let z = random_between(-1., 1.);
+    pub fn random_unit_vector(sampler: &mut dyn Sampler) -> Self {
+        let t = sampler.random_between(0., 2. * floats::consts::PI);
+        let z = sampler.random_between(-1., 1.);
         let r = (1. - z.powi(2)).sqrt();
         Vec3::new(r * t.cos(), r * t.sin(), z)
     }
@@ -158,7 +152,7 @@ impl Vec3 {
 
     /// 计算反射光线的方向
     pub fn reflect(&self, normal: &Vec3) -> Self {
-        *self - *normal * (2.0 * self.dot(&normal))
+        *self - *normal * (2.0 * self.dot(normal))
     }
 
     /// 计算折射光线的方向
@@ -169,6 +163,29 @@ impl Vec3 {
         r_out_perp + r_out_parallel
     }
 
+    /// 与单位向量 `self` 构成右手正交基的另外两个单位向量，见 Duff 等人 2017 年的构造
+    pub fn orthonormal_basis(&self) -> (Vec3, Vec3) {
+        let sign = (1. as Float).copysign(self.z);
+        let a = -1. / (sign + self.z);
+        let b = self.x * self.y * a;
+        (
+            Vec3::new(1. + sign * self.x * self.x * a, sign * b, -sign * self.x),
+            Vec3::new(b, sign + self.y * self.y * a, -self.y),
+        )
+    }
+
+    /// 将以 `self` 为 z 轴的局部坐标变换到世界坐标，`self` 须为单位向量
+    pub fn from_local(&self, local: &Vec3) -> Vec3 {
+        let (u, v) = self.orthonormal_basis();
+        u * local.x + v * local.y + *self * local.z
+    }
+
+    /// `from_local` 的逆变换
+    pub fn to_local(&self, world: &Vec3) -> Vec3 {
+        let (u, v) = self.orthonormal_basis();
+        Vec3::new(u.dot(world), v.dot(world), self.dot(world))
+    }
+
     pub fn into_color(self) -> Color {
         Color::rgb(self.x, self.y, self.z)
     }
@@ -224,12 +241,24 @@ impl Neg for Vec3 {
 
 #[test]
 fn test_vec3() {
-    let u = Vec3::random();
-    let v = Vec3::random();
+    let mut sampler = crate::sampler::RandomSampler::new(0);
+    let u = Vec3::random(&mut sampler);
+    let v = Vec3::random(&mut sampler);
     assert_eq!(v.dot(&v), v.length_squared());
     assert_eq!(u.dot(&-v), -u.dot(&v));
 }
 
+#[test]
+fn test_orthonormal_basis() {
+    let mut sampler = crate::sampler::RandomSampler::new(0);
+    for _ in 0..100 {
+        let w = Vec3::random_unit_vector(&mut sampler);
+        let (u, v) = w.orthonormal_basis();
+        assert!(u.dot(&v).abs() < 1e-5 && u.dot(&w).abs() < 1e-5 && v.dot(&w).abs() < 1e-5);
+        assert!((u.cross(&v) - w).length() < 1e-5);
+    }
+}
+
 #[test]
 fn test_reflect() {
     let x = Vec3::new(1., -1., 0.);
@@ -0,0 +1,33 @@
+use std::sync::Arc;
+
+use crate::hittable::{Hittable, HittableList};
+use crate::vec3::Vec3;
+
+/// 光线未击中任何物体时的背景
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Background {
+    /// 自白色至天蓝色的渐变，按光线方向的 y 分量插值
+    Sky,
+    /// 纯色
+    Solid(Vec3),
+}
+
+impl Background {
+    pub fn color(&self, direction: &Vec3) -> Vec3 {
+        match self {
+            Background::Sky => {
+                let t = 0.5 * (direction.y + 1.0);
+                Vec3::new(0.5, 0.7, 1.0) * t + (1. - t)
+            }
+            Background::Solid(color) => *color,
+        }
+    }
+}
+
+/// 待渲染的场景内容
+pub struct World {
+    pub objects: Arc<dyn Hittable>,
+    /// 可直接采样的光源，它们同时包含在 `objects` 中
+    pub lights: HittableList,
+    pub background: Background,
+}